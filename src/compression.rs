@@ -0,0 +1,88 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// Content encodings supported by the blog, in order of preference.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+pub const ENCODINGS: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+/// Effort spent compressing, the fast level is used when compressing the
+/// responses while serving them.
+#[derive(Copy, Clone, Debug)]
+pub enum Level {
+    Fast,
+    Best,
+}
+
+impl Encoding {
+    /// Name of the encoding in the `Accept-Encoding` and `Content-Encoding`
+    /// headers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Extension of the files compressed with the encoding.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    pub fn compress(&self, content: &[u8], level: Level) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let quality = match level {
+                    Level::Fast => 5,
+                    Level::Best => 11,
+                };
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, quality, 22);
+                writer.write_all(content)?;
+                Ok(writer.into_inner())
+            }
+            Encoding::Gzip => {
+                let level = match level {
+                    Level::Fast => Compression::fast(),
+                    Level::Best => Compression::best(),
+                };
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Returns the path of the version of the file compressed with the given
+/// encoding, e.g.: `style.css.br`.
+pub fn compressed_path(path: &Path, encoding: Encoding) -> PathBuf {
+    let mut compressed_path = path.as_os_str().to_owned();
+    compressed_path.push(".");
+    compressed_path.push(encoding.extension());
+    PathBuf::from(compressed_path)
+}
+
+/// Returns true if it is worth compressing content of the given mime type,
+/// e.g.: html, css or json, but not images or fonts which are already
+/// compressed.
+pub fn is_compressible(mime_type: &str) -> bool {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/")
+        || essence.ends_with("+xml")
+        || essence.ends_with("+json")
+        || matches!(
+            essence,
+            "application/json" | "application/javascript" | "application/xml"
+        )
+}
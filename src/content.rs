@@ -6,10 +6,14 @@ use regex::Regex;
 use serde::{self, Deserialize, Deserializer};
 use std::{
     cmp::Ordering,
+    collections::BTreeMap,
     path::{self, PathBuf},
 };
 
-use crate::errors::Error;
+use crate::{
+    errors::Error,
+    urls::{self, absolute_url, post_url, BaseUrl},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct Blog {
@@ -19,44 +23,106 @@ pub struct Blog {
     pub year: u16,
     #[serde(default)]
     pub home_content: String,
+    /// Sources of the posts, sorted by date from the newest to the oldest.
+    #[serde(skip)]
+    pub posts: Vec<PostSource>,
+    /// Paths of the post assets relative to the posts directory.
+    #[serde(skip)]
+    pub post_assets: Vec<PathBuf>,
+    #[serde(skip)]
+    pub pages: Vec<Page>,
+    /// Pattern used to build the url of the posts, e.g.:
+    /// `/:year/:month/:slug/`.
     #[serde(default)]
-    pub posts: Vec<Post>,
+    pub permalink: Option<String>,
+    /// Public url of the blog, e.g.: `https://example.com/blog/`.
     #[serde(default)]
-    pub post_assets: Vec<PathBuf>,
+    pub base_url: Option<String>,
+    /// Values of the `Cache-Control` header used by the server for each
+    /// route, e.g.: `posts: public, max-age=60`.
+    #[serde(default)]
+    pub cache: BTreeMap<String, String>,
+    /// Security headers added to the responses, they replace the default
+    /// ones, e.g.: `Referrer-Policy: no-referrer`.
+    #[serde(default)]
+    pub security_headers: BTreeMap<String, String>,
+    /// Front matter keys not known by the blog engine.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 impl Blog {
-    pub fn read_from(dir: &str) -> Result<Blog, Error> {
-        let post_items = read_post_files(dir)?;
-        let mut posts: Vec<Post> = Vec::new();
+    /// Reads the blog from the given content directory, the
+    /// `default_permalink` is used if the blog does not define one, and the
+    /// `base_url`, if any, replaces the one defined by the blog. Only the
+    /// metadata of the posts is read, the content of a post is read with
+    /// [`read_post_file`].
+    pub fn read_from(
+        dir: &str,
+        default_permalink: &str,
+        base_url: Option<&str>,
+    ) -> Result<Blog, Error> {
+        let mut blog = read_blog_file(dir, base_url)?;
+        let permalink = blog.permalink.as_deref().unwrap_or(default_permalink);
+        let posts_dir_path = path::Path::new(&dir).join("posts");
+        let mut entries: Vec<PostEntry> = Vec::new();
+        read_posts_dir(&posts_dir_path, path::Path::new(""), &mut entries)?;
+        let mut posts: Vec<PostSource> = Vec::new();
         let mut post_assets: Vec<PathBuf> = Vec::new();
-        for item in post_items {
-            match item {
-                PostItem::Content(post) => posts.push(post),
-                PostItem::Asset(asset) => post_assets.push(asset),
+        for entry in entries {
+            match entry {
+                PostEntry::Post(post_path, assets_dir) => {
+                    posts.push(read_post_source(post_path, assets_dir, permalink)?)
+                }
+                PostEntry::Asset(asset) => post_assets.push(asset),
             };
         }
-        posts.sort_by(|a, b| b.date.0.cmp(&a.date.0));
-        let mut blog = read_blog_file(dir)?;
+        posts.sort_by(|a, b| b.metadata.date.cmp(&a.metadata.date));
+        // Posts can live in different directories, and can define their own
+        // slugs, so ensure no two posts end up with the same url.
+        let mut urls = std::collections::HashSet::new();
+        for post in &posts {
+            if !urls.insert(post.metadata.url.as_str()) {
+                return Err(Error::Undefined(format!(
+                    "more than one post with the url: {}",
+                    post.metadata.url
+                )));
+            }
+        }
+        let pages = read_pages(dir, &blog.base_url())?;
+        // Pages are published in the root, as the posts with a permalink
+        // like `/:slug`.
+        for page in &pages {
+            let page_url = urls::page_url(&page.slug);
+            if urls.contains(page_url.as_str()) {
+                return Err(Error::Undefined(format!(
+                    "the page {} has the same url as a post: {}",
+                    page.path.display(),
+                    page_url
+                )));
+            }
+        }
+        check_aliases(&posts, &pages)?;
         blog.posts = posts;
         blog.post_assets = post_assets;
+        blog.pages = pages;
         Ok(blog)
     }
+
+    pub(crate) fn base_url(&self) -> BaseUrl {
+        BaseUrl::new(self.base_url.as_deref().unwrap_or_default())
+    }
 }
 
-pub(crate) fn read_blog_file(dir: &str) -> Result<Blog, Error> {
+/// Reads the blog file in the given content directory, the `base_url`, if
+/// any, replaces the one defined in the file.
+pub(crate) fn read_blog_file(dir: &str, base_url: Option<&str>) -> Result<Blog, Error> {
     let blog_file = path::Path::new(&dir).join("blog.md");
     let blog_contents = std::fs::read_to_string(blog_file.clone())?;
     let (content, front_matter) = split_content(&blog_contents);
     if front_matter.is_empty() {
         return Err(Error::NoFrontMatter(blog_file.to_string_lossy().into()));
     }
-    let mut options = Options::empty();
-    options.insert(Options::ENABLE_STRIKETHROUGH);
-    let parser = MDParser::new_ext(&content, options);
-    let parser = process_markdown(parser);
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
     let matter = Matter::<YAML>::new();
     let result = matter.parse(&front_matter);
     let data = match result.data {
@@ -66,13 +132,32 @@ pub(crate) fn read_blog_file(dir: &str) -> Result<Blog, Error> {
         }
     };
     let mut blog: Blog = data.deserialize()?;
+    if let Some(base_url) = base_url {
+        blog.base_url = Some(base_url.into());
+    }
+
+    // The base url is needed to know the path of the images.
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = MDParser::new_ext(&content, options);
+    let assets_url = urls::post_assets_url(path::Path::new(""));
+    let parser = process_markdown(parser, blog.base_url().link(&assets_url));
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, parser);
     blog.home_content = html_output;
     Ok(blog)
 }
 
-enum PostItem {
-    Content(Post),
-    Asset(PathBuf),
+/// The file of a post together with its metadata.
+#[derive(Debug)]
+pub struct PostSource {
+    pub path: PathBuf,
+    /// Directory, relative to the posts directory, with the assets of the
+    /// post.
+    pub assets_dir: PathBuf,
+    /// The front matter of the post as written in the file.
+    pub front_matter: String,
+    pub metadata: PostMetadata,
 }
 
 #[derive(Deserialize, Debug)]
@@ -87,10 +172,33 @@ pub struct Post {
     #[serde(default)]
     pub favorite: bool,
     #[serde(default)]
-    pub file_name: String,
+    pub slug: String,
+    #[serde(skip)]
+    pub url: String,
+    /// Old urls of the post that must redirect to the current one.
+    #[serde(default)]
+    pub aliases: Vec<String>,
     pub author: String,
     #[serde(default)]
     pub year: String,
+    /// Front matter keys not known by the blog engine.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// A standalone page, like an "About" page, that is not part of the list of
+/// posts.
+#[derive(Deserialize, Debug)]
+pub struct Page {
+    pub title: String,
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Deserialize, Debug, Eq, PartialEq)]
@@ -102,7 +210,16 @@ pub struct PostMetadata {
     pub summary: String,
     pub author: String,
     #[serde(default)]
-    pub file_name: String,
+    pub favorite: bool,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(skip)]
+    pub url: String,
+    /// Old urls of the post that must redirect to the current one.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -143,65 +260,168 @@ impl PartialOrd for DateTime {
     }
 }
 
-fn read_post_files(content_path: &str) -> Result<Vec<PostItem>, Error> {
-    let posts_dir_path = path::Path::new(&content_path).join("posts");
-    let mut post_items: Vec<PostItem> = Vec::new();
-    for entry in std::fs::read_dir(posts_dir_path).map_err(Error::from)? {
+/// Name of the markdown file that contains the post of a page bundle, that is
+/// a directory with a post and its own assets.
+const BUNDLE_INDEX: &str = "index.md";
+
+/// An entry found in the posts directory.
+enum PostEntry {
+    /// The path of a post file and the directory, relative to the posts
+    /// directory, that contains the assets of the post.
+    Post(PathBuf, PathBuf),
+    /// The path of an asset relative to the posts directory.
+    Asset(PathBuf),
+}
+
+pub(crate) fn read_posts_metadata(
+    posts_path: &str,
+    permalink: &str,
+) -> Result<Vec<PostMetadata>, Error> {
+    let mut entries: Vec<PostEntry> = Vec::new();
+    read_posts_dir(
+        path::Path::new(posts_path),
+        path::Path::new(""),
+        &mut entries,
+    )?;
+    let mut posts_metadata: Vec<PostMetadata> = Vec::new();
+    for entry in entries {
+        if let PostEntry::Post(post_path, _) = entry {
+            let post_path = post_path.to_str().unwrap_or("");
+            let metadata = read_post_metadata(post_path, permalink)?;
+            posts_metadata.push(metadata);
+        }
+    }
+    Ok(posts_metadata)
+}
+
+/// Finds the file of the post published under the given url, returns the
+/// path of the file and the directory, relative to the posts directory, of its
+/// assets.
+pub(crate) fn find_post_file(
+    posts_path: &str,
+    permalink: &str,
+    url: &str,
+) -> Result<Option<(PathBuf, PathBuf)>, Error> {
+    let mut entries: Vec<PostEntry> = Vec::new();
+    read_posts_dir(
+        path::Path::new(posts_path),
+        path::Path::new(""),
+        &mut entries,
+    )?;
+    let url = url.trim_end_matches('/');
+    for entry in entries {
+        if let PostEntry::Post(post_path, assets_dir) = entry {
+            let metadata = read_post_metadata(&post_path.to_string_lossy(), permalink)?;
+            if metadata.url.trim_end_matches('/') == url {
+                return Ok(Some((post_path, assets_dir)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Collects the posts and assets in the given directory, relative to the
+/// posts directory, and in all its subdirectories. A directory containing an
+/// `index.md` file is a page bundle: the `index.md` is the post and the rest
+/// of the files in the directory are its assets.
+fn read_posts_dir(
+    posts_path: &path::Path,
+    current_dir: &path::Path,
+    entries: &mut Vec<PostEntry>,
+) -> Result<(), Error> {
+    let current_full_path = posts_path.join(current_dir);
+    let bundle_index = current_full_path.join(BUNDLE_INDEX);
+    if current_dir.components().next().is_some() && bundle_index.is_file() {
+        entries.push(PostEntry::Post(bundle_index, current_dir.to_path_buf()));
+        return read_bundle_assets(posts_path, current_dir, entries);
+    }
+    for entry in std::fs::read_dir(current_full_path).map_err(Error::from)? {
         let entry = entry.map_err(Error::from)?;
         let entry_type = entry.file_type().map_err(Error::from)?;
+        let entry_path = current_dir.join(entry.file_name());
+        if entry_type.is_dir() {
+            read_posts_dir(posts_path, &entry_path, entries)?;
+            continue;
+        }
         if !entry_type.is_file() {
             continue;
         }
-        if let Some(ext) = entry.path().extension() {
-            let ext = ext.to_str().unwrap_or("");
-            if ext != "md" {
-                // We consider any file with an extension different to "md"
-                // an asset, so we just push the path.
-                let asset_path = entry.path();
-                post_items.push(PostItem::Asset(asset_path));
-                continue;
-            }
-            let post_path = entry.path();
-            let post_path = post_path.to_str().unwrap_or("");
-            let post = read_post_file(post_path)?;
-            post_items.push(PostItem::Content(post));
+        // We consider any file with an extension different to "md"
+        // an asset.
+        if entry_path.extension().unwrap_or_default() != "md" {
+            entries.push(PostEntry::Asset(entry_path));
+            continue;
         }
+        entries.push(PostEntry::Post(entry.path(), current_dir.to_path_buf()));
     }
-
-    Ok(post_items)
+    Ok(())
 }
 
-pub(crate) fn read_posts_metadata(posts_path: &str) -> Result<Vec<PostMetadata>, Error> {
-    let mut posts_metadata: Vec<PostMetadata> = Vec::new();
-    for entry in std::fs::read_dir(posts_path).map_err(Error::from)? {
+fn read_bundle_assets(
+    posts_path: &path::Path,
+    current_dir: &path::Path,
+    entries: &mut Vec<PostEntry>,
+) -> Result<(), Error> {
+    for entry in std::fs::read_dir(posts_path.join(current_dir)).map_err(Error::from)? {
         let entry = entry.map_err(Error::from)?;
         let entry_type = entry.file_type().map_err(Error::from)?;
-        if !entry_type.is_file() {
+        let entry_path = current_dir.join(entry.file_name());
+        if entry_type.is_dir() {
+            read_bundle_assets(posts_path, &entry_path, entries)?;
             continue;
         }
-        if let Some(ext) = entry.path().extension() {
-            let ext = ext.to_str().unwrap_or("");
-            if ext != "md" {
-                continue;
-            }
-            let post_path = entry.path();
-            let post_path = post_path.to_str().unwrap_or("");
-            let metadata = read_post_metadata(post_path)?;
-            posts_metadata.push(metadata);
+        if entry_type.is_file() && entry_path.extension().unwrap_or_default() != "md" {
+            entries.push(PostEntry::Asset(entry_path));
         }
     }
-    Ok(posts_metadata)
+    Ok(())
 }
 
-pub(crate) fn read_post_metadata(post_path: &str) -> Result<PostMetadata, Error> {
-    let blog_contents = std::fs::read_to_string(post_path)?;
+/// Returns the slug of a post from the path of its file, for page bundles
+/// the slug is the name of the bundle directory.
+fn post_slug(post_path: &path::Path) -> String {
+    let file_name = post_path.file_name().unwrap_or_default();
+    let slug = match (file_name == BUNDLE_INDEX, post_path.parent()) {
+        (true, Some(bundle_dir)) => bundle_dir.file_name(),
+        _ => post_path.file_stem(),
+    };
+    slug.unwrap_or_default().to_string_lossy().into()
+}
+
+pub(crate) fn read_post_metadata(post_path: &str, permalink: &str) -> Result<PostMetadata, Error> {
+    let blog_contents = read_content_file(path::Path::new(post_path))?;
     let (_, front_matter) = split_content(&blog_contents);
+    parse_post_metadata(post_path, &front_matter, permalink)
+}
+
+fn read_post_source(
+    post_path: PathBuf,
+    assets_dir: PathBuf,
+    permalink: &str,
+) -> Result<PostSource, Error> {
+    let blog_contents = std::fs::read_to_string(&post_path)?;
+    let (_, front_matter) = split_content(&blog_contents);
+    let metadata = parse_post_metadata(&post_path.to_string_lossy(), &front_matter, permalink)?;
+    let source = PostSource {
+        path: post_path,
+        assets_dir,
+        front_matter,
+        metadata,
+    };
+    Ok(source)
+}
+
+fn parse_post_metadata(
+    post_path: &str,
+    front_matter: &str,
+    permalink: &str,
+) -> Result<PostMetadata, Error> {
     if front_matter.is_empty() {
         return Err(Error::NoFrontMatter(post_path.into()));
     }
 
     let matter = Matter::<YAML>::new();
-    let result = matter.parse(&front_matter);
+    let result = matter.parse(front_matter);
     let data = match result.data {
         Some(data) => data,
         None => {
@@ -209,24 +429,240 @@ pub(crate) fn read_post_metadata(post_path: &str) -> Result<PostMetadata, Error>
         }
     };
     let mut metadata: PostMetadata = data.deserialize()?;
-    metadata.file_name = PathBuf::from(post_path)
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .into();
+    if metadata.slug.is_empty() {
+        metadata.slug = post_slug(path::Path::new(post_path));
+    }
+    metadata.url = checked_post_url(
+        path::Path::new(post_path),
+        permalink,
+        &metadata.slug,
+        &metadata.date,
+    )?;
+    metadata.aliases = checked_aliases(path::Path::new(post_path), &metadata.aliases)?;
     Ok(metadata)
 }
 
-pub(crate) fn read_post_file(post_path: &str) -> Result<Post, Error> {
-    let blog_contents = std::fs::read_to_string(post_path)?;
+/// Returns the url of a post, ensuring the file it's published to is inside
+/// the root of the site.
+fn checked_post_url(
+    post_path: &path::Path,
+    permalink: &str,
+    slug: &str,
+    date: &DateTime,
+) -> Result<String, Error> {
+    if !urls::is_valid_slug(slug) {
+        return Err(Error::Undefined(format!(
+            "invalid slug for the post {}: {}",
+            post_path.display(),
+            slug
+        )));
+    }
+    let url = post_url(permalink, slug, date);
+    if !urls::is_valid_url(&url) {
+        return Err(Error::Undefined(format!(
+            "invalid url for the post {}, check the permalink: {}",
+            post_path.display(),
+            url
+        )));
+    }
+    Ok(url)
+}
+
+/// Returns the aliases of a post as absolute urls, ensuring the files of their
+/// redirections are inside the root of the site.
+fn checked_aliases(post_path: &path::Path, aliases: &[String]) -> Result<Vec<String>, Error> {
+    aliases
+        .iter()
+        .map(|alias| {
+            let alias = absolute_url(alias);
+            match urls::is_valid_url(&alias) {
+                true => Ok(alias),
+                false => Err(Error::Undefined(format!(
+                    "invalid alias for the post {}: {}",
+                    post_path.display(),
+                    alias
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// Reads a post from its file, the `assets_dir` is the directory, relative to
+/// the posts directory, that contains the assets of the post.
+pub(crate) fn read_post_file(
+    post_path: &path::Path,
+    assets_dir: &path::Path,
+    permalink: &str,
+    base_url: &BaseUrl,
+) -> Result<Post, Error> {
+    let blog_contents = read_content_file(post_path)?;
     let (content, front_matter) = split_content(&blog_contents);
     if front_matter.is_empty() {
-        return Err(Error::NoFrontMatter(post_path.into()));
+        return Err(Error::NoFrontMatter(post_path.to_string_lossy().into()));
+    }
+    let matter = Matter::<YAML>::new();
+    let result = matter.parse(&front_matter);
+    let data = match result.data {
+        Some(data) => data,
+        None => {
+            return Err(Error::NoFrontMatter(post_path.to_string_lossy().into()));
+        }
+    };
+    let mut post: Post = data.deserialize()?;
+    if post.slug.is_empty() {
+        post.slug = post_slug(post_path);
+    }
+    post.url = checked_post_url(post_path, permalink, &post.slug, &post.date)?;
+    post.aliases = checked_aliases(post_path, &post.aliases)?;
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let parser = MDParser::new_ext(&content, options);
+    let assets_url = urls::post_assets_url(assets_dir);
+    let parser = process_markdown(parser, base_url.link(&assets_url));
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, parser);
+    post.content = html_output;
+    post.year = post.date.0.year().to_string();
+    Ok(post)
+}
+
+/// Urls an alias can't have, as its redirection would replace the home, the
+/// not found page or the route of the posts of the server.
+const RESERVED_ALIASES: [&str; 3] = ["/index", "/404", "/posts"];
+
+/// Ensures the aliases of the posts don't replace any other url of the blog:
+/// the pack command writes a redirection page for each of them, and the
+/// server adds a route for each of them.
+fn check_aliases(posts: &[PostSource], pages: &[Page]) -> Result<(), Error> {
+    // The server serves the same content with and without the trailing
+    // slash.
+    let mut used_urls: std::collections::HashSet<String> = posts
+        .iter()
+        .map(|post| post.metadata.url.trim_end_matches('/').to_string())
+        .chain(pages.iter().map(|page| urls::page_url(&page.slug)))
+        .collect();
+    for post in posts {
+        for alias in &post.metadata.aliases {
+            let alias_url = alias.trim_end_matches('/');
+            let in_assets = [urls::ASSETS_DIR, urls::POST_ASSETS_DIR].iter().any(|dir| {
+                let dir_url = format!("/{}", dir);
+                alias_url == dir_url || alias_url.starts_with(&format!("{}/", dir_url))
+            });
+            if in_assets
+                || RESERVED_ALIASES.contains(&alias_url)
+                || !used_urls.insert(alias_url.to_string())
+            {
+                return Err(Error::Undefined(format!(
+                    "the alias {} of the post {} is already used by the blog",
+                    alias,
+                    post.path.display()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Slugs a page can't have, as its file would replace one of the files or
+/// directories generated for the blog.
+const RESERVED_PAGE_SLUGS: [&str; 4] = ["index", "404", urls::ASSETS_DIR, urls::POST_ASSETS_DIR];
+
+/// Reads all the pages in the `pages` directory of the content directory. As
+/// pages are optional, if the directory doesn't exist no pages are returned.
+pub(crate) fn read_pages(content_path: &str, base_url: &BaseUrl) -> Result<Vec<Page>, Error> {
+    let mut pages: Vec<Page> = Vec::new();
+    for page_path in page_files(path::Path::new(content_path))? {
+        pages.push(read_page_file(&page_path.to_string_lossy(), base_url)?);
+    }
+    pages.sort_by(|a, b| a.slug.cmp(&b.slug));
+    for pair in pages.windows(2) {
+        if pair[0].slug == pair[1].slug {
+            return Err(Error::Undefined(format!(
+                "more than one page with the slug: {}",
+                pair[0].slug
+            )));
+        }
+    }
+    Ok(pages)
+}
+
+/// Returns the paths of the files of the pages in the content directory.
+pub(crate) fn page_files(content_path: &path::Path) -> Result<Vec<PathBuf>, Error> {
+    let pages_dir_path = content_path.join("pages");
+    if !pages_dir_path.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut page_files = Vec::new();
+    for entry in std::fs::read_dir(pages_dir_path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        if entry.path().extension().unwrap_or_default() != "md" {
+            continue;
+        }
+        page_files.push(entry.path());
+    }
+    page_files.sort();
+    Ok(page_files)
+}
+
+/// Front matter keys needed to know the url of a page without reading all
+/// of it.
+#[derive(Deserialize)]
+struct PageSlug {
+    #[serde(default)]
+    slug: String,
+}
+
+/// Returns the slug of the page in the given file, without rendering it.
+pub(crate) fn read_page_slug(page_path: &path::Path) -> Result<String, Error> {
+    let page_contents = read_content_file(page_path)?;
+    let (_, front_matter) = split_content(&page_contents);
+    let matter = Matter::<YAML>::new();
+    let data = match matter.parse(&front_matter).data {
+        Some(data) => data,
+        None => return Err(Error::NoFrontMatter(page_path.to_string_lossy().into())),
+    };
+    let page: PageSlug = data.deserialize()?;
+    page_slug(page_path, page.slug)
+}
+
+/// Returns the slug of a page, the one in its front matter or, if it has
+/// none, the name of its file. The slug is the name of the file the page is
+/// published to, so it can't collide with the other files of the blog.
+fn page_slug(page_path: &path::Path, front_matter_slug: String) -> Result<String, Error> {
+    let slug = match front_matter_slug.is_empty() {
+        true => page_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into(),
+        false => front_matter_slug,
+    };
+    let is_valid = urls::is_valid_slug(&slug) && !RESERVED_PAGE_SLUGS.contains(&slug.as_str());
+    match is_valid {
+        true => Ok(slug),
+        false => Err(Error::Undefined(format!(
+            "invalid slug for the page {}: {}",
+            page_path.display(),
+            slug
+        ))),
+    }
+}
+
+pub(crate) fn read_page_file(page_path: &str, base_url: &BaseUrl) -> Result<Page, Error> {
+    let page_contents = read_content_file(path::Path::new(page_path))?;
+    let (content, front_matter) = split_content(&page_contents);
+    if front_matter.is_empty() {
+        return Err(Error::NoFrontMatter(page_path.into()));
     }
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     let parser = MDParser::new_ext(&content, options);
-    let parser = process_markdown(parser);
+    let assets_url = urls::post_assets_url(path::Path::new(""));
+    let parser = process_markdown(parser, base_url.link(&assets_url));
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
 
@@ -235,27 +671,24 @@ pub(crate) fn read_post_file(post_path: &str) -> Result<Post, Error> {
     let data = match result.data {
         Some(data) => data,
         None => {
-            return Err(Error::NoFrontMatter(post_path.into()));
+            return Err(Error::NoFrontMatter(page_path.into()));
         }
     };
-    let mut post: Post = data.deserialize()?;
-    post.content = html_output;
-    let post_path = path::Path::new(&post_path);
-    let mut post_path = path::PathBuf::from(post_path);
-    post_path.set_extension("html");
-    let file_name = post_path
-        .file_name()
-        .map_or_else(|| "".to_string(), |path| path.to_string_lossy().to_string());
-    post.file_name = file_name;
-    post.year = post.date.0.year().to_string();
-    Ok(post)
+    let mut page: Page = data.deserialize()?;
+    page.content = html_output;
+    page.slug = page_slug(path::Path::new(page_path), page.slug)?;
+    page.path = PathBuf::from(page_path);
+    Ok(page)
 }
 
-fn process_markdown<'a>(parser: MDParser<'a, 'a>) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+fn process_markdown<'a>(
+    parser: MDParser<'a, 'a>,
+    assets_prefix: String,
+) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
     let mut heading_text: Option<String> = None;
     // We modify the stream of events of the parser so:
     // 1. We generate heading by ourselves so we can add anchors to them.
-    // 2. Add the "post_assets" directory to the path of the images.
+    // 2. Add the directory of the post assets to the path of the images.
     // TODO: Find a way for adding the anchors to the headings without having
     // to directly generate the HTML for them.
     let parser = parser.map(move |event| match &event {
@@ -282,7 +715,7 @@ fn process_markdown<'a>(parser: MDParser<'a, 'a>) -> Box<dyn Iterator<Item = Eve
             )))
         }
         Event::Start(pulldown_cmark::Tag::Image(link_type, url, title)) => {
-            let url = format!("post_assets/{}", url);
+            let url = format!("{}/{}", assets_prefix, url);
             let tag = pulldown_cmark::Tag::Image(*link_type, CowStr::from(url), title.clone());
             Event::Start(tag)
         }
@@ -302,6 +735,15 @@ fn heading_anchor(text: &str) -> String {
         .replace(' ', "-")
 }
 
+/// Reads the file of a post or a page. Returns `Error::NotFound` if it
+/// doesn't exist, e.g.: when it's removed while serving it.
+fn read_content_file(path: &path::Path) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => Error::NotFound(format!("{}: {}", path.display(), err)),
+        _ => Error::from(err),
+    })
+}
+
 fn split_content(content: &str) -> (String, String) {
     let delimiter = "---";
     let rest = match content.starts_with(delimiter) {
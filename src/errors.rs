@@ -2,8 +2,12 @@ use std::fmt::Display;
 
 pub enum Error {
     Undefined(String),
+    /// A file that does not exist, e.g.: the file of a post removed while
+    /// serving it.
+    NotFound(String),
     NoFrontMatter(String),
     NoBlogTemplateFound,
+    NoPageTemplateFound,
 }
 
 impl Error {
@@ -16,8 +20,10 @@ impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Undefined(message) => write!(f, "{}", message),
+            Error::NotFound(message) => write!(f, "{}", message),
             Error::NoFrontMatter(file) => write!(f, "no front matter in {}", file),
             Error::NoBlogTemplateFound => write!(f, "no main template file found"),
+            Error::NoPageTemplateFound => write!(f, "no page template file found"),
         }
     }
 }
@@ -39,3 +45,9 @@ impl From<serde_json::Error> for Error {
         Error::Undefined(error.to_string())
     }
 }
+
+impl From<notify::Error> for Error {
+    fn from(error: notify::Error) -> Self {
+        Error::Undefined(error.to_string())
+    }
+}
@@ -1,11 +1,14 @@
 use clap::{Parser, Subcommand};
+mod compression;
 mod content;
 mod errors;
 mod pack;
 use pack::PackCommand;
+mod security_headers;
 mod serve;
-use serve::ServeCommand;
+use serve::{ServeCommand, ServeStaticCommand};
 mod templates;
+mod urls;
 
 fn main() {
     let cli = Cli::parse();
@@ -30,6 +33,8 @@ enum Commands {
     Pack(PackCommand),
     /// Dynamically serves the contents of the blog.
     Serve(ServeCommand),
+    /// Serves a directory generated by the pack command.
+    ServeStatic(ServeStaticCommand),
 }
 
 impl From<Commands> for Box<dyn CommandRun> {
@@ -37,6 +42,7 @@ impl From<Commands> for Box<dyn CommandRun> {
         match command {
             Commands::Pack(command) => Box::new(command),
             Commands::Serve(command) => Box::new(command),
+            Commands::ServeStatic(command) => Box::new(command),
         }
     }
 }
@@ -0,0 +1,66 @@
+use std::{fs, path::Path};
+
+use sha2::{Digest, Sha256};
+
+use crate::errors::Error;
+
+/// Hash of all the inputs used to generate a file of the output directory,
+/// if the key of a file doesn't change between two packs the file doesn't
+/// need to be generated again.
+pub(crate) struct BuildKey(Sha256);
+
+impl BuildKey {
+    pub(crate) fn new() -> BuildKey {
+        BuildKey(Sha256::new())
+    }
+
+    pub(crate) fn add(mut self, data: impl AsRef<[u8]>) -> BuildKey {
+        let data = data.as_ref();
+        // The length avoids different inputs producing the same sequence of
+        // bytes, e.g.: "ab" + "c" and "a" + "bc".
+        self.0.update((data.len() as u64).to_le_bytes());
+        self.0.update(data);
+        self
+    }
+
+    pub(crate) fn add_file(self, path: &Path) -> Result<BuildKey, Error> {
+        let content = fs::read(path)?;
+        Ok(self.add(content))
+    }
+
+    /// Adds all the files in the `current_path` of the `dir` directory, and
+    /// in its subdirectories, except the ones in the directories named
+    /// `exclude`.
+    pub(crate) fn add_dir(
+        mut self,
+        dir: &Path,
+        current_path: &Path,
+        exclude: &str,
+    ) -> Result<BuildKey, Error> {
+        let mut entries = fs::read_dir(dir.join(current_path))?.collect::<Result<Vec<_>, _>>()?;
+        // The order of the entries returned by read_dir is not guaranteed.
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let entry_type = entry.file_type()?;
+            let entry_path = current_path.join(entry.file_name());
+            if entry_type.is_dir() && entry.file_name() != exclude {
+                self = self.add_dir(dir, &entry_path, exclude)?;
+                continue;
+            }
+            if entry_type.is_file() {
+                self = self
+                    .add(entry_path.to_string_lossy().as_bytes())
+                    .add_file(&entry.path())?;
+            }
+        }
+        Ok(self)
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.0
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+    time::Instant,
+};
+
+use chrono::Datelike;
+
+use clap::{Args, ValueEnum};
+use pulldown_cmark::escape::escape_html;
+use rayon::prelude::*;
+
+use crate::{
+    compression::{self, Level, ENCODINGS},
+    content,
+    errors::Error,
+    security_headers::{SecurityHeaders, SecurityHeadersArgs},
+    templates,
+    urls::{self, BaseUrl, ASSETS_DIR, DEFAULT_PERMALINK, POST_ASSETS_DIR},
+    CommandRun,
+};
+
+mod build_key;
+mod output;
+mod watch;
+use build_key::BuildKey;
+use output::{list_files, output_paths, Staging};
+
+const POSTS_DIR: &str = "posts";
+const BLOG_FILE: &str = "blog.md";
+const NOT_FOUND_FILE: &str = "404.html";
+const HEADERS_FILE: &str = "_headers";
+/// Extensions of the generated files written also compressed when the
+/// precompress flag is set.
+const PRECOMPRESSED_EXTENSIONS: [&str; 5] = ["html", "css", "js", "xml", "json"];
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum RedirectsFormat {
+    /// A `_redirects` file, as used by Netlify and similar hosts.
+    Netlify,
+    /// A `redirects.map` file with the entries of an nginx map block.
+    Nginx,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct PackCommand {
+    /// Path to a directory containing the blog templates.
+    templates: String,
+    /// Path to a directory containing the blog contents.
+    content: String,
+    /// Path to a directory for the generated content files.
+    output: String,
+    /// Also write a file with the redirects of the aliases of the posts.
+    #[arg(long, value_enum)]
+    redirects: Option<RedirectsFormat>,
+    /// Use the output directory even if it contains files not generated by a
+    /// previous pack, overwriting them if needed.
+    #[arg(long)]
+    force: bool,
+    /// Generate all the files again instead of reusing the ones whose inputs
+    /// didn't change since the previous pack. The `site.build_time` of a
+    /// reused page is the time of the pack that rendered it.
+    #[arg(long)]
+    full: bool,
+    /// Number of threads used to render the posts and pages and to copy the
+    /// assets, 0 uses one per CPU.
+    #[arg(short, long, default_value_t = 0)]
+    jobs: usize,
+    /// Keep running and pack the blog again when the templates or the
+    /// contents change.
+    #[arg(long)]
+    watch: bool,
+    /// Public url of the blog, it replaces the one defined in the blog file.
+    #[arg(long)]
+    base_url: Option<String>,
+    /// Also write the HTML, CSS, JS, XML and JSON files compressed with gzip
+    /// and brotli, for hosts that serve them directly, e.g.: nginx with
+    /// gzip_static.
+    #[arg(long)]
+    precompress: bool,
+    /// Also write a `_headers` file with the security headers, as used by
+    /// Netlify and similar hosts.
+    #[arg(long)]
+    headers_file: bool,
+    #[command(flatten)]
+    security_headers: SecurityHeadersArgs,
+}
+
+impl CommandRun for PackCommand {
+    fn run(&self) {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()
+            .map_err(|err| Error::Undefined(format!("error creating the worker pool: {}", err)));
+        let result = pool.and_then(|pool| {
+            pool.install(|| {
+                if self.watch {
+                    self.watch()
+                } else {
+                    self.pack()
+                }
+            })
+        });
+        if let Err(err) = result {
+            err.fatal();
+            exit(1);
+        }
+    }
+}
+
+impl PackCommand {
+    /// Packs the blog every time the templates or the contents change,
+    /// reporting the errors instead of exiting.
+    fn watch(&self) -> Result<(), Error> {
+        let timed_pack = || {
+            let start = Instant::now();
+            match self.pack() {
+                Ok(()) => println!("packed in {:.2?}", start.elapsed()),
+                Err(err) => eprintln!("error packing the blog: {err}"),
+            }
+        };
+        timed_pack();
+        // The watched paths are canonicalized, so the ignored ones must be
+        // too.
+        let templates_path = fs::canonicalize(&self.templates)?;
+        let content_path = fs::canonicalize(&self.content)?;
+        let output = Path::new(&self.output);
+        let output = match output.file_name() {
+            Some(name) => {
+                let parent = output
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new("."));
+                fs::canonicalize(parent)?.join(name)
+            }
+            None => output.to_path_buf(),
+        };
+        println!("watching for changes");
+        watch::watch(
+            &[&templates_path, &content_path],
+            &output_paths(&output)?,
+            timed_pack,
+        )
+    }
+
+    fn pack(&self) -> Result<(), Error> {
+        // All the files are generated in a staging directory that replaces
+        // the output directory once the pack has finished. The files whose
+        // inputs didn't change since the previous pack are reused.
+        let output = Staging::new(Path::new(&self.output), self.force, !self.full)?;
+
+        // Copy the template assets directory to the output directory.
+        let templates_path = PathBuf::from(&self.templates);
+        let template_assets = list_files(&templates_path, Path::new(ASSETS_DIR))?;
+        for_each_in_order(&template_assets, |asset_path| {
+            output.copy(&templates_path.join(asset_path), asset_path)
+        })?;
+
+        // Load the templates of rhe blog.
+        let blog_templates = templates::Blog::read_from_dir(&self.templates)?;
+
+        // Read the content of the blog.
+        let blog_content =
+            content::Blog::read_from(&self.content, DEFAULT_PERMALINK, self.base_url.as_deref())?;
+        let permalink = blog_content
+            .permalink
+            .as_deref()
+            .unwrap_or(DEFAULT_PERMALINK);
+        let base_url = blog_content.base_url();
+        let site = templates::SiteTemplateModel::from(&blog_content);
+
+        // Every rendered page depends on the templates, on the front matter
+        // of the blog, as it's available to all the templates, on the base
+        // url, used in all the links, and on the version of wrustblog, that
+        // renders them and is available as `site.version`. The build time is
+        // left out, or no page could ever be reused.
+        let blog_key = BuildKey::new()
+            .add(env!("CARGO_PKG_VERSION"))
+            .add_dir(&templates_path, Path::new(""), ASSETS_DIR)?
+            .add_file(&Path::new(&self.content).join(BLOG_FILE))?
+            .add(base_url.url())
+            .finish();
+
+        // Render main page, it must be generated again when the metadata of
+        // any post changes.
+        let main_page_path = urls::url_file_path(urls::ROOT_URL);
+        let main_page_key = blog_content
+            .posts
+            .iter()
+            .fold(BuildKey::new().add(&blog_key), |key, post| {
+                key.add(&post.metadata.url).add(&post.front_matter)
+            })
+            .finish();
+        if !output.reuse(&main_page_path, &main_page_key)? {
+            let main_template_model = templates::MainTemplateModel {
+                author: blog_content.author.clone(),
+                title: blog_content.title.clone(),
+                home_content: blog_content.home_content.clone(),
+                twitter: blog_content.twitter.clone(),
+                year: blog_content.year,
+                posts: blog_content
+                    .posts
+                    .iter()
+                    .map(|post| main_page_post_template_model(&post.metadata, &site, &base_url))
+                    .collect(),
+                canonical_url: base_url.absolute(urls::ROOT_URL),
+                site: site.clone(),
+                extra: templates::Extra::from(&blog_content.extra),
+            };
+            let main_page_content = blog_templates.render_main(&main_template_model);
+            output.write(&main_page_path, main_page_key, main_page_content)?;
+        }
+
+        // Render blog posts.
+        for_each_in_order(&blog_content.posts, |post_source| {
+            let post_path = urls::url_file_path(&post_source.metadata.url);
+            let post_key = BuildKey::new()
+                .add(&blog_key)
+                .add(&post_source.metadata.url)
+                .add(post_source.path.to_string_lossy().as_bytes())
+                .add_file(&post_source.path)?
+                .finish();
+            if output.reuse(&post_path, &post_key)? {
+                return Ok(());
+            }
+            let post = content::read_post_file(
+                &post_source.path,
+                &post_source.assets_dir,
+                permalink,
+                &base_url,
+            )?;
+            let post_template_model = templates::PostTemplateModel {
+                title: post.title.clone(),
+                date: templates::DateTime(post.date.0),
+                tags: post
+                    .tags
+                    .iter()
+                    .map(|tag| templates::Tag(tag.0.clone()))
+                    .collect(),
+                summary: post.summary.clone(),
+                root_page: base_url.link(urls::ROOT_URL),
+                content: post.content.clone(),
+                favorite: post.favorite,
+                file_name: base_url.link(&post.url),
+                canonical_url: base_url.absolute(&post.url),
+                author: post.author.clone(),
+                year: post.year.clone(),
+                site: site.clone(),
+                extra: templates::Extra::from(&post.extra),
+            };
+            let post_content = blog_templates.render_post(&post_template_model);
+            output.write(&post_path, post_key, post_content)
+        })?;
+
+        // Write the pages redirecting the aliases of the posts.
+        let mut redirects: Vec<(String, String)> = Vec::new();
+        for post in &blog_content.posts {
+            for alias in &post.metadata.aliases {
+                write_alias_page(&output, alias, &base_url.absolute(&post.metadata.url))?;
+                redirects.push((base_url.link(alias), base_url.link(&post.metadata.url)));
+            }
+        }
+        if let Some(format) = self.redirects {
+            write_redirects_file(&output, format, &redirects)?;
+        }
+
+        if self.headers_file {
+            let security_headers =
+                SecurityHeaders::new(&blog_content.security_headers, &self.security_headers);
+            let content = security_headers.headers_file();
+            let key = BuildKey::new().add(&content).finish();
+            output.write(Path::new(HEADERS_FILE), key, content)?;
+        }
+
+        // Render standalone pages, the same as when serving the blog, pages
+        // are not published if there is no template for them.
+        let pages: &[content::Page] = match blog_templates.has_page_template() {
+            true => &blog_content.pages,
+            false => {
+                if !blog_content.pages.is_empty() {
+                    eprintln!("warning: no page template found, the pages are not packed");
+                }
+                &[]
+            }
+        };
+        for_each_in_order(pages, |page| {
+            let page_url = urls::page_url(&page.slug);
+            let page_path = urls::url_file_path(&page_url);
+            let page_key = BuildKey::new()
+                .add(&blog_key)
+                .add_file(&page.path)?
+                .finish();
+            if output.reuse(&page_path, &page_key)? {
+                return Ok(());
+            }
+            let page_template_model = templates::PageTemplateModel {
+                title: page.title.clone(),
+                root_page: base_url.link(urls::ROOT_URL),
+                canonical_url: base_url.absolute(&page_url),
+                content: page.content.clone(),
+                slug: page.slug.clone(),
+                site: site.clone(),
+                extra: templates::Extra::from(&page.extra),
+            };
+            let page_content = blog_templates.render_page(&page_template_model)?;
+            output.write(&page_path, page_key, page_content)
+        })?;
+
+        // Render the not found page, used by most static hosts.
+        let not_found_path = Path::new(NOT_FOUND_FILE);
+        if !output.reuse(not_found_path, &blog_key)? {
+            let not_found_model = templates::ErrorTemplateModel {
+                status: 404,
+                root_page: base_url.link(urls::ROOT_URL),
+                site: site.clone(),
+            };
+            if let Some(content) = blog_templates.render_not_found(&not_found_model) {
+                output.write(not_found_path, blog_key.clone(), content)?;
+            }
+        }
+
+        // Copy the assets of the posts to the post assets directory, keeping
+        // the directory structure they have in the posts directory.
+        let post_assets_path = PathBuf::from(POST_ASSETS_DIR);
+        let posts_path = Path::new(&self.content).join(POSTS_DIR);
+        for_each_in_order(&blog_content.post_assets, |asset_path| {
+            let src_asset_path = posts_path.join(asset_path);
+            output.copy(&src_asset_path, &post_assets_path.join(asset_path))
+        })?;
+
+        // Compress the text files once all of them are generated.
+        if self.precompress {
+            let files: Vec<(PathBuf, String)> = output
+                .files()
+                .into_iter()
+                .filter(|(path, _)| {
+                    path.extension().map_or(false, |ext| {
+                        PRECOMPRESSED_EXTENSIONS.iter().any(|e| ext == *e)
+                    })
+                })
+                .collect();
+            for_each_in_order(&files, |(path, key)| precompress(&output, path, key))?;
+        }
+
+        output.publish()
+    }
+}
+
+/// Runs the given function for every item using the worker pool. When it
+/// fails for more than one item, the error of the first one in the slice is
+/// returned, so errors are reported as if the items were processed one by one.
+fn for_each_in_order<T, F>(items: &[T], f: F) -> Result<(), Error>
+where
+    T: Sync,
+    F: Fn(&T) -> Result<(), Error> + Sync + Send,
+{
+    let results: Vec<Result<(), Error>> = items.par_iter().map(f).collect();
+    results.into_iter().collect()
+}
+
+/// Writes the versions of the generated file in the given path compressed
+/// with every encoding, skipping the ones that are not smaller than the file.
+/// The compressed files get the modification time of the file, as the hosts
+/// serving them use it to check they are not stale.
+fn precompress(output: &Staging, path: &Path, key: &str) -> Result<(), Error> {
+    let staged_path = output.staged_path(path);
+    let modified = fs::metadata(&staged_path)?.modified()?;
+    let content = fs::read(&staged_path)?;
+    for encoding in ENCODINGS {
+        let compressed_path = compression::compressed_path(path, encoding);
+        let compressed_key = BuildKey::new().add(key).add(encoding.name()).finish();
+        if !output.reuse(&compressed_path, &compressed_key)? {
+            let compressed = encoding.compress(&content, Level::Best)?;
+            if compressed.len() >= content.len() {
+                continue;
+            }
+            output.write(&compressed_path, compressed_key, compressed)?;
+        }
+        fs::File::options()
+            .write(true)
+            .open(output.staged_path(&compressed_path))?
+            .set_modified(modified)?;
+    }
+    Ok(())
+}
+
+/// Returns the template model of a post in the main page, where only the
+/// metadata of the posts is available.
+fn main_page_post_template_model(
+    metadata: &content::PostMetadata,
+    site: &templates::SiteTemplateModel,
+    base_url: &BaseUrl,
+) -> templates::PostTemplateModel {
+    templates::PostTemplateModel {
+        title: metadata.title.clone(),
+        date: templates::DateTime(metadata.date.0),
+        tags: metadata
+            .tags
+            .iter()
+            .map(|tag| templates::Tag(tag.0.clone()))
+            .collect(),
+        summary: metadata.summary.clone(),
+        root_page: base_url.link(urls::ROOT_URL),
+        content: "".into(),
+        favorite: metadata.favorite,
+        file_name: base_url.link(&metadata.url),
+        canonical_url: base_url.absolute(&metadata.url),
+        author: metadata.author.clone(),
+        year: metadata.date.0.year().to_string(),
+        site: site.clone(),
+        extra: templates::Extra::from(&metadata.extra),
+    }
+}
+
+/// Writes a page that redirects from the given alias to the post, using a
+/// meta refresh so it works in any static host.
+fn write_alias_page(output: &Staging, alias: &str, post_url: &str) -> Result<(), Error> {
+    // The alias page is written in the path of the alias, so it can't point
+    // outside the output directory.
+    if !urls::is_valid_url(alias) {
+        return Err(Error::Undefined(format!(
+            "invalid alias for the post {}: {}",
+            post_url, alias
+        )));
+    }
+    let alias_file_path = urls::url_file_path(alias);
+    let mut target = String::new();
+    escape_html(&mut target, post_url)?;
+    let alias_page_content = format!(
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>Redirecting to {target}</title>\n\
+        <link rel=\"canonical\" href=\"{target}\">\n\
+        <meta http-equiv=\"refresh\" content=\"0; url={target}\">\n\
+        </head>\n\
+        <body><a href=\"{target}\">{target}</a></body>\n\
+        </html>\n"
+    );
+    let key = BuildKey::new().add(&alias_page_content).finish();
+    output.write(&alias_file_path, key, alias_page_content)
+}
+
+fn write_redirects_file(
+    output: &Staging,
+    format: RedirectsFormat,
+    redirects: &[(String, String)],
+) -> Result<(), Error> {
+    let (file_name, content) = match format {
+        RedirectsFormat::Netlify => (
+            "_redirects",
+            redirects
+                .iter()
+                .map(|(from, to)| format!("{} {} 301\n", from, to))
+                .collect::<String>(),
+        ),
+        RedirectsFormat::Nginx => (
+            "redirects.map",
+            redirects
+                .iter()
+                .map(|(from, to)| format!("{} {};\n", from, to))
+                .collect::<String>(),
+        ),
+    };
+    let key = BuildKey::new().add(&content).finish();
+    output.write(Path::new(file_name), key, content)
+}
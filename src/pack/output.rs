@@ -0,0 +1,482 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::build_key::BuildKey;
+use crate::errors::Error;
+
+const MANIFEST_FILE: &str = ".wrustblog-manifest.json";
+
+/// Version of the format of the manifest, it must be increased when the build
+/// keys change, so the files generated by a previous version are not reused.
+const MANIFEST_VERSION: u32 = 1;
+
+/// List of the files generated by the pack command, it's stored in the output
+/// directory so the next pack knows which files it can safely replace, and
+/// which ones it can reuse because their inputs didn't change.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Manifest {
+    /// Manifests without version were written before the build keys.
+    #[serde(default)]
+    version: u32,
+    /// Paths of the generated files relative to the output directory, with
+    /// the build key of the inputs used to generate them.
+    pub files: BTreeMap<String, String>,
+}
+
+/// Formats of the manifest written by the previous versions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredManifest {
+    Current(Manifest),
+    /// Only the paths of the generated files.
+    Listed {
+        files: BTreeSet<String>,
+    },
+}
+
+impl Default for Manifest {
+    fn default() -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// Reads the manifest of the given output directory. The build keys of a
+    /// manifest written by a different version are discarded, so its files
+    /// are known but not reused, and a manifest that can't be read is
+    /// considered empty.
+    pub(crate) fn read_from(dir: &Path) -> Result<Option<Manifest>, Error> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if !manifest_path.is_file() {
+            return Ok(None);
+        }
+        let stored = fs::read_to_string(&manifest_path)
+            .map_err(Error::from)
+            .and_then(|manifest| serde_json::from_str(&manifest).map_err(Error::from));
+        let files = match stored {
+            Ok(StoredManifest::Current(manifest)) if manifest.version == MANIFEST_VERSION => {
+                return Ok(Some(manifest))
+            }
+            Ok(StoredManifest::Current(manifest)) => manifest.files.into_keys().collect(),
+            Ok(StoredManifest::Listed { files }) => files,
+            Err(err) => {
+                eprintln!(
+                    "warning: ignoring the manifest {}: {}",
+                    manifest_path.display(),
+                    err
+                );
+                BTreeSet::new()
+            }
+        };
+        // An empty build key never matches the key of a file.
+        let files = files
+            .into_iter()
+            .map(|file| (file, String::new()))
+            .collect();
+        Ok(Some(Manifest {
+            version: MANIFEST_VERSION,
+            files,
+        }))
+    }
+
+    fn write_to(&self, dir: &Path) -> Result<(), Error> {
+        let manifest = serde_json::to_string_pretty(self)?;
+        fs::write(dir.join(MANIFEST_FILE), manifest)?;
+        Ok(())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(&manifest_path(path))
+    }
+
+    /// Returns true if any of the files is inside the given directory, or in
+    /// one of its subdirectories.
+    fn contains_files_in(&self, dir: &Path) -> bool {
+        let prefix = format!("{}/", manifest_path(dir));
+        self.files.keys().any(|file| file.starts_with(&prefix))
+    }
+}
+
+/// Directory where the pack command writes the generated files before
+/// publishing all of them at once in the output directory. Files can be
+/// written to it from different threads.
+pub(crate) struct Staging {
+    dir: PathBuf,
+    output: PathBuf,
+    force: bool,
+    incremental: bool,
+    previous: Option<Manifest>,
+    manifest: Mutex<Manifest>,
+    published: bool,
+}
+
+impl Staging {
+    /// Creates an empty staging directory for the given output directory. It
+    /// fails if the output directory contains files not generated by a
+    /// previous pack, unless `force` is set. When `incremental` is set the
+    /// files of the previous pack can be reused.
+    pub(crate) fn new(output: &Path, force: bool, incremental: bool) -> Result<Staging, Error> {
+        let previous = check_output_dir(output, force)?;
+        let dir = sibling_dir(output, "staging")?;
+        // A staging directory can only exist if a previous pack was
+        // interrupted.
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        let staging = Staging {
+            dir,
+            output: output.to_path_buf(),
+            force,
+            incremental,
+            previous,
+            manifest: Mutex::new(Manifest::default()),
+            published: false,
+        };
+        Ok(staging)
+    }
+
+    /// Reuses the file in the given path, relative to the output directory,
+    /// generated by the previous pack if it was generated with the same
+    /// build key. Returns false if the file can't be reused.
+    pub(crate) fn reuse(&self, path: &Path, key: &str) -> Result<bool, Error> {
+        let manifest_path = manifest_path(path);
+        let previous_key = match (&self.previous, self.incremental) {
+            (Some(previous), true) => previous.files.get(&manifest_path),
+            _ => None,
+        };
+        let previous_file = self.output.join(path);
+        if previous_key.map(String::as_str) != Some(key) || !previous_file.is_file() {
+            return Ok(false);
+        }
+        // Hard linking avoids writing the file again, but it's not supported
+        // in every file system.
+        let dest = self.dest_path(path)?;
+        if fs::hard_link(&previous_file, &dest).is_err() {
+            fs::copy(&previous_file, &dest)?;
+        }
+        self.add_to_manifest(manifest_path, key.into());
+        Ok(true)
+    }
+
+    /// Writes a file, generated from the inputs with the given build key, in
+    /// the given path relative to the output directory.
+    pub(crate) fn write(
+        &self,
+        path: &Path,
+        key: String,
+        content: impl AsRef<[u8]>,
+    ) -> Result<(), Error> {
+        let dest = self.dest_path(path)?;
+        fs::write(dest, content)?;
+        self.add_to_manifest(manifest_path(path), key);
+        Ok(())
+    }
+
+    /// Copies a file to the given path relative to the output directory.
+    pub(crate) fn copy(&self, src: &Path, path: &Path) -> Result<(), Error> {
+        let key = BuildKey::new().add_file(src)?.finish();
+        if self.reuse(path, &key)? {
+            return Ok(());
+        }
+        let dest = self.dest_path(path)?;
+        fs::copy(src, dest)?;
+        self.add_to_manifest(manifest_path(path), key);
+        Ok(())
+    }
+
+    /// Replaces the output directory with the staging directory. The files and
+    /// directories in the output directory that were not generated by the
+    /// previous pack are kept: they are hard linked, or copied if that fails,
+    /// into the staging directory before the replacement.
+    ///
+    /// On Linux both directories are exchanged atomically. Elsewhere, or if
+    /// the file system doesn't support it, the output directory is renamed
+    /// and the staging directory takes its place, as a directory can't be
+    /// renamed over a non empty one, so for a moment there is no output
+    /// directory.
+    pub(crate) fn publish(mut self) -> Result<(), Error> {
+        self.manifest().write_to(&self.dir)?;
+        if !self.output.exists() {
+            fs::rename(&self.dir, &self.output)?;
+            self.published = true;
+            return Ok(());
+        }
+        self.check_overwrites()?;
+        keep_unknown_files(
+            &self.output,
+            &self.dir,
+            Path::new(""),
+            self.previous.as_ref(),
+        )?;
+        #[cfg(target_os = "linux")]
+        if exchange_dirs(&self.dir, &self.output).is_ok() {
+            self.published = true;
+            // The staging directory has the previous output now.
+            fs::remove_dir_all(&self.dir)?;
+            return Ok(());
+        }
+        let old_output = sibling_dir(&self.output, "old")?;
+        if old_output.exists() {
+            return Err(Error::Undefined(format!(
+                "the directory {} exists, probably from an interrupted pack, \
+                check its contents and remove it",
+                old_output.display()
+            )));
+        }
+        fs::rename(&self.output, &old_output)?;
+        if let Err(err) = fs::rename(&self.dir, &self.output) {
+            fs::rename(&old_output, &self.output)?;
+            return Err(Error::from(err));
+        }
+        self.published = true;
+        fs::remove_dir_all(&old_output)?;
+        Ok(())
+    }
+
+    /// Ensures the generated files don't overwrite files in the output
+    /// directory that were not generated by a previous pack.
+    fn check_overwrites(&self) -> Result<(), Error> {
+        if self.force {
+            return Ok(());
+        }
+        for file in self.manifest().files.keys() {
+            let is_generated = self
+                .previous
+                .as_ref()
+                .map_or(false, |previous| previous.files.contains_key(file));
+            if !is_generated && self.output.join(file).exists() {
+                return Err(Error::Undefined(format!(
+                    "refusing to overwrite {} in the output directory, use --force to overwrite it",
+                    file
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the paths, relative to the output directory, and the build
+    /// keys of the files generated so far.
+    pub(crate) fn files(&self) -> Vec<(PathBuf, String)> {
+        self.manifest()
+            .files
+            .iter()
+            .map(|(path, key)| (PathBuf::from(path), key.clone()))
+            .collect()
+    }
+
+    /// Returns the path where the file in the given path, relative to the
+    /// output directory, is generated.
+    pub(crate) fn staged_path(&self, path: &Path) -> PathBuf {
+        self.dir.join(path)
+    }
+
+    fn add_to_manifest(&self, path: String, key: String) {
+        self.manifest().files.insert(path, key);
+    }
+
+    fn manifest(&self) -> MutexGuard<'_, Manifest> {
+        // The lock is never held while doing something that can panic.
+        self.manifest.lock().unwrap()
+    }
+
+    fn dest_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        let dest = self.dir.join(path);
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(dest)
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        // If the pack failed, remove the partially generated files.
+        if !self.published {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Checks the output directory can be used by the pack command and returns
+/// the manifest of the previous pack, if any.
+fn check_output_dir(output: &Path, force: bool) -> Result<Option<Manifest>, Error> {
+    if !output.exists() {
+        return Ok(None);
+    }
+    if !output.is_dir() {
+        return Err(Error::Undefined(format!(
+            "the output path {} is not a directory",
+            output.display()
+        )));
+    }
+    let previous = Manifest::read_from(output)?;
+    let is_empty = fs::read_dir(output)?.next().is_none();
+    if previous.is_none() && !is_empty && !force {
+        return Err(Error::Undefined(format!(
+            "the output directory {} is not empty and was not generated by pack, \
+            use --force to use it anyway",
+            output.display()
+        )));
+    }
+    Ok(previous)
+}
+
+/// Returns the paths of the files in the `current_path` of the `dir`
+/// directory, and in all its subdirectories, relative to `dir`.
+pub(crate) fn list_files(dir: &Path, current_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir.join(current_path))? {
+        let entry = entry?;
+        let entry_type = entry.file_type()?;
+        let entry_path = current_path.join(entry.file_name());
+        if entry_type.is_file() {
+            files.push(entry_path);
+            continue;
+        }
+        if entry_type.is_dir() {
+            files.extend(list_files(dir, &entry_path)?);
+        }
+    }
+    // Sort the files so they are always processed in the same order.
+    files.sort();
+    Ok(files)
+}
+
+/// Links the files and directories in the `current_path` of the output
+/// directory, and in all its subdirectories, that were not generated by the
+/// previous pack into the staging directory. The directories keep their
+/// permissions in the staging directory.
+fn keep_unknown_files(
+    output: &Path,
+    staging: &Path,
+    current_path: &Path,
+    previous: Option<&Manifest>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(output.join(current_path))? {
+        let entry = entry?;
+        let entry_type = entry.file_type()?;
+        let entry_path = current_path.join(entry.file_name());
+        let dest = staging.join(&entry_path);
+        if entry_type.is_dir() {
+            let has_generated_files =
+                previous.map_or(false, |previous| previous.contains_files_in(&entry_path));
+            // A directory without generated files is linked as a whole,
+            // including its empty subdirectories.
+            if !has_generated_files && !dest.exists() {
+                fs::create_dir_all(staging.join(current_path))?;
+                link_dir(&entry.path(), &dest)?;
+                continue;
+            }
+            keep_unknown_files(output, staging, &entry_path, previous)?;
+            if dest.is_dir() {
+                fs::set_permissions(&dest, entry.metadata()?.permissions())?;
+            }
+            continue;
+        }
+        let is_generated = entry_path == Path::new(MANIFEST_FILE)
+            || previous.map_or(false, |previous| previous.contains(&entry_path));
+        // The files that exist in the staging directory can only be
+        // overwritten ones, when the force flag is set.
+        if is_generated || dest.exists() {
+            continue;
+        }
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        link_file(&entry.path(), &dest)?;
+    }
+    Ok(())
+}
+
+/// Recreates the directory `src` at `dest`, linking all its files.
+fn link_dir(src: &Path, dest: &Path) -> Result<(), Error> {
+    fs::create_dir(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let entry_dest = dest.join(entry.file_name());
+        match entry.file_type()?.is_dir() {
+            true => link_dir(&entry.path(), &entry_dest)?,
+            false => link_file(&entry.path(), &entry_dest)?,
+        }
+    }
+    // The permissions are set last, they could forbid adding the files.
+    fs::set_permissions(dest, fs::metadata(src)?.permissions())?;
+    Ok(())
+}
+
+/// Hard links the file `src` at `dest`, or copies it if the file system
+/// doesn't support hard links.
+fn link_file(src: &Path, dest: &Path) -> Result<(), Error> {
+    if fs::hard_link(src, dest).is_err() {
+        fs::copy(src, dest)?;
+    }
+    Ok(())
+}
+
+/// Exchanges two directories atomically with `renameat2`.
+#[cfg(target_os = "linux")]
+fn exchange_dirs(a: &Path, b: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    // SAFETY: both paths are NUL terminated strings that outlive the call.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Returns the paths written by the pack command: the output directory and
+/// the directories used to build it.
+pub(crate) fn output_paths(output: &Path) -> Result<Vec<PathBuf>, Error> {
+    Ok(vec![
+        output.to_path_buf(),
+        sibling_dir(output, "staging")?,
+        sibling_dir(output, "old")?,
+    ])
+}
+
+/// Returns a hidden directory next to the output directory with the given
+/// suffix.
+fn sibling_dir(output: &Path, suffix: &str) -> Result<PathBuf, Error> {
+    let name = match output.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => {
+            return Err(Error::Undefined(format!(
+                "invalid output directory: {}",
+                output.display()
+            )))
+        }
+    };
+    let parent = output.parent().unwrap_or_else(|| Path::new(""));
+    Ok(parent.join(format!(".{}.{}", name, suffix)))
+}
+
+/// Returns the path as stored in the manifest, always using slashes as
+/// separator.
+fn manifest_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
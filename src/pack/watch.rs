@@ -0,0 +1,48 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{event::EventKind, RecursiveMode, Watcher};
+
+use crate::errors::Error;
+
+/// Time without changes to wait for before rebuilding, so a burst of writes,
+/// like the ones done by editors when saving a file, triggers only one build.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the given directories and calls `rebuild` every time something
+/// changes in them. Changes in the `ignored` paths, or in their children, are
+/// ignored. It only returns if the directories can't be watched.
+pub(crate) fn watch(
+    dirs: &[&Path],
+    ignored: &[PathBuf],
+    mut rebuild: impl FnMut(),
+) -> Result<(), Error> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+    loop {
+        let event = match receiver.recv() {
+            Ok(Ok(event)) => event,
+            // An error in one of the events, e.g. a file that can't be
+            // watched, doesn't stop watching the rest.
+            Ok(Err(err)) => {
+                eprintln!("error watching for changes: {err}");
+                continue;
+            }
+            Err(_) => return Err(Error::Undefined("stopped watching for changes".into())),
+        };
+        let is_ignored = |path: &PathBuf| ignored.iter().any(|ignored| path.starts_with(ignored));
+        if matches!(event.kind, EventKind::Access(_))
+            || (!event.paths.is_empty() && event.paths.iter().all(is_ignored))
+        {
+            continue;
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+        rebuild();
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use clap::Args;
+
+/// Header sent only when enabled, as it makes the browsers refuse to connect
+/// to the blog without https for a long time.
+const STRICT_TRANSPORT_SECURITY: &str = "Strict-Transport-Security";
+
+/// Security headers suitable for a static blog: only its own scripts, styles
+/// and fonts, images from anywhere, and no access to the device features.
+const DEFAULT_HEADERS: [(&str, &str); 5] = [
+    (
+        "Content-Security-Policy",
+        "default-src 'self'; img-src 'self' https: data:; style-src 'self' 'unsafe-inline'; \
+        object-src 'none'; base-uri 'self'; frame-ancestors 'self'",
+    ),
+    ("X-Content-Type-Options", "nosniff"),
+    ("Referrer-Policy", "strict-origin-when-cross-origin"),
+    (
+        "Permissions-Policy",
+        "camera=(), geolocation=(), microphone=(), payment=(), usb=()",
+    ),
+    (
+        STRICT_TRANSPORT_SECURITY,
+        "max-age=31536000; includeSubDomains",
+    ),
+];
+
+#[derive(Args, Debug)]
+pub(crate) struct SecurityHeadersArgs {
+    /// Security header added to the responses, it replaces the default one
+    /// and the one defined in the blog file, an empty value removes it.
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+    /// Add the Strict-Transport-Security header, only for blogs served
+    /// through https.
+    #[arg(long)]
+    hsts: bool,
+}
+
+/// Security headers added to all the responses of the blog.
+#[derive(Debug)]
+pub(crate) struct SecurityHeaders {
+    headers: Vec<(String, String)>,
+}
+
+impl SecurityHeaders {
+    /// Returns the default headers overridden, in order, by the ones defined
+    /// in the blog file and by the ones in the command line arguments.
+    pub(crate) fn new(
+        blog_headers: &BTreeMap<String, String>,
+        args: &SecurityHeadersArgs,
+    ) -> SecurityHeaders {
+        let mut headers: Vec<(String, String)> = DEFAULT_HEADERS
+            .iter()
+            .filter(|(name, _)| args.hsts || *name != STRICT_TRANSPORT_SECURITY)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let configured = blog_headers
+            .iter()
+            .chain(args.headers.iter().map(|(name, value)| (name, value)));
+        for (name, value) in configured {
+            // Header names are case insensitive.
+            headers.retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+            if !value.is_empty() {
+                headers.push((name.clone(), value.clone()));
+            }
+        }
+        SecurityHeaders { headers }
+    }
+
+    pub(crate) fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the content of a `_headers` file, as used by Netlify and
+    /// similar hosts, that adds the headers to all the files.
+    pub(crate) fn headers_file(&self) -> String {
+        let headers: String = self
+            .headers
+            .iter()
+            .map(|(name, value)| format!("  {}: {}\n", name, value))
+            .collect();
+        format!("/*\n{}", headers)
+    }
+}
+
+/// Parses a header given in the command line, e.g.:
+/// `Referrer-Policy=no-referrer`.
+fn parse_header(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected NAME=VALUE, got: {}", arg)),
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use wruster::http::{headers::Header, Response, StatusCode};
+
+use crate::errors::Error;
+
+/// Routes with their own cache policy, the names are the ones used to
+/// configure the policies, e.g.: `--cache posts="max-age=60"`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum CacheRoute {
+    Home,
+    Posts,
+    Pages,
+    Assets,
+    PostAssets,
+}
+
+const HOME: &str = "home";
+const POSTS: &str = "posts";
+const PAGES: &str = "pages";
+const ASSETS: &str = "assets";
+/// Assets with a hash in their names, e.g.: `style.3f2a9c1b.css`, their
+/// content never changes, so they can be cached forever.
+const FINGERPRINTED_ASSETS: &str = "fingerprinted_assets";
+const POST_ASSETS: &str = "post_assets";
+
+/// Policy used for every route when caching is disabled.
+const NO_CACHE: &str = "no-cache";
+
+const DEFAULT_POLICIES: [(&str, &str); 6] = [
+    (HOME, "public, max-age=300"),
+    (POSTS, "public, max-age=3600"),
+    (PAGES, "public, max-age=3600"),
+    (ASSETS, "public, max-age=86400"),
+    (FINGERPRINTED_ASSETS, "public, max-age=31536000, immutable"),
+    (POST_ASSETS, "public, max-age=86400"),
+];
+
+/// Values of the `Cache-Control` header of the responses of each route.
+pub(crate) struct CachePolicies {
+    policies: BTreeMap<&'static str, String>,
+}
+
+impl CachePolicies {
+    /// Returns the default policies overridden, in order, by the ones defined
+    /// in the blog file and by the `overrides`. If `no_cache` is true, for
+    /// instance while writing the blog, all the routes use `no-cache`.
+    pub(crate) fn new(
+        blog_policies: &BTreeMap<String, String>,
+        overrides: &[(String, String)],
+        no_cache: bool,
+    ) -> Result<CachePolicies, Error> {
+        let mut policies: BTreeMap<&'static str, String> = DEFAULT_POLICIES
+            .iter()
+            .map(|(route, policy)| (*route, policy.to_string()))
+            .collect();
+        let configured = blog_policies
+            .iter()
+            .chain(overrides.iter().map(|(route, policy)| (route, policy)));
+        for (route, policy) in configured {
+            let route = DEFAULT_POLICIES
+                .iter()
+                .map(|(name, _)| *name)
+                .find(|name| name == route)
+                .ok_or_else(|| {
+                    Error::Undefined(format!("unknown route in cache policies: {}", route))
+                })?;
+            policies.insert(route, policy.clone());
+        }
+        if no_cache {
+            policies
+                .values_mut()
+                .for_each(|policy| *policy = NO_CACHE.into());
+        }
+        Ok(CachePolicies { policies })
+    }
+
+    /// Returns the policy for a request with the given uri served by the
+    /// route.
+    fn policy(&self, route: CacheRoute, uri: &str) -> &str {
+        let name = match route {
+            CacheRoute::Home => HOME,
+            CacheRoute::Posts => POSTS,
+            CacheRoute::Pages => PAGES,
+            CacheRoute::Assets if is_fingerprinted(uri) => FINGERPRINTED_ASSETS,
+            CacheRoute::Assets => ASSETS,
+            CacheRoute::PostAssets => POST_ASSETS,
+        };
+        &self.policies[name]
+    }
+
+    /// Adds the `Cache-Control` header to the successful responses of the
+    /// route, the error responses are not cached.
+    pub(crate) fn apply(&self, route: CacheRoute, uri: &str, mut response: Response) -> Response {
+        if !matches!(
+            response.status,
+            StatusCode::OK | StatusCode::PartialContent | StatusCode::NotModified
+        ) {
+            return response;
+        }
+        response.headers.add(Header {
+            name: String::from("Cache-Control"),
+            value: self.policy(route, uri).into(),
+        });
+        response
+    }
+}
+
+/// Returns true if the name of the file in the uri contains a hash, e.g.:
+/// `main.3f2a9c1b.js` or `main-3f2a9c1b.js`.
+fn is_fingerprinted(uri: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"[.-][0-9a-fA-F]{8,}\.[^./]+$").unwrap();
+    }
+    RE.is_match(uri)
+}
+
+/// Parses a cache policy given in the command line, e.g.:
+/// `assets=public, max-age=60`.
+pub(crate) fn parse_cache_policy(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((route, policy)) if !route.is_empty() => {
+            Ok((route.trim().to_string(), policy.trim().to_string()))
+        }
+        _ => Err(format!("expected ROUTE=POLICY, got: {}", arg)),
+    }
+}
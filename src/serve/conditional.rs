@@ -0,0 +1,150 @@
+use std::{fs, time::UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use wruster::http::{
+    headers::{Header, Headers},
+    Request, Response, StatusCode,
+};
+
+use super::{
+    encoding::{decoded_etag, encoded_etag},
+    request_header,
+};
+use crate::compression::Encoding;
+
+/// Format of the dates in the http headers, e.g.:
+/// `Wed, 21 Oct 2015 07:28:00 GMT`.
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Validators of the content of a response, used to answer the conditional
+/// requests of the clients that already have the content.
+pub(crate) struct Validators {
+    /// Strong entity tag of the representation of the content served.
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Returns the validators of a generated content, the hash of the content
+    /// is its entity tag. It has no last modified date, as the content
+    /// depends on many files, e.g.: the templates and the blog file.
+    pub(crate) fn from_content(content: impl AsRef<[u8]>) -> Validators {
+        let hash = Sha256::digest(content.as_ref());
+        Validators {
+            etag: format!("\"{:x}\"", hash),
+            last_modified: None,
+        }
+    }
+
+    /// Returns the validators of a file from its metadata, so the file is not
+    /// read to answer the conditional requests. The entity tag changes when
+    /// the size, the modification time or the inode of the file change, and
+    /// the last modified date is the modification time of the file.
+    pub(crate) fn from_file(metadata: &fs::Metadata) -> Validators {
+        let modified = metadata.modified().ok();
+        let modified_nanos = modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_nanos());
+        Validators {
+            etag: format!(
+                "\"{:x}-{:x}-{:x}\"",
+                metadata.len(),
+                modified_nanos,
+                file_id(metadata)
+            ),
+            last_modified: modified.map(DateTime::from),
+        }
+    }
+
+    /// Returns the validators of the content compressed with the given
+    /// encoding.
+    pub(crate) fn encoded(self, encoding: Encoding) -> Validators {
+        Validators {
+            etag: encoded_etag(&self.etag, encoding),
+            last_modified: self.last_modified,
+        }
+    }
+
+    /// Returns true if the copy of the content the client has, according to
+    /// the conditional headers of the request, is still valid.
+    pub(crate) fn not_modified(&self, request: &Request) -> bool {
+        // When both headers are present If-None-Match takes precedence.
+        if let Some(if_none_match) = request_header(request, "If-None-Match") {
+            // The compressed versions of the content are equivalent to it.
+            let etag = decoded_etag(&self.etag);
+            return if_none_match.split(',').any(|if_none_match| {
+                let if_none_match = if_none_match.trim();
+                if_none_match == "*" || decoded_etag(if_none_match.trim_start_matches("W/")) == etag
+            });
+        }
+        let if_modified_since = request_header(request, "If-Modified-Since")
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+        match (if_modified_since, self.last_modified) {
+            // The dates in the headers have a precision of seconds.
+            (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the ranges of the request can be served, that is, if
+    /// the request has no `If-Range` header or the content didn't change
+    /// since the client got the validator in it. The entity tags are
+    /// compared as strong validators, so the ranges of a compressed version
+    /// of the content can't be combined with the ones of another version.
+    pub(crate) fn if_range_matches(&self, request: &Request) -> bool {
+        let if_range = match request_header(request, "If-Range") {
+            Some(if_range) => if_range.trim(),
+            None => return true,
+        };
+        // Only strong validators can be used to combine ranges.
+        if if_range.starts_with("W/") {
+            return false;
+        }
+        if if_range.starts_with('"') {
+            return if_range == self.etag;
+        }
+        match (DateTime::parse_from_rfc2822(if_range), self.last_modified) {
+            (Ok(date), Some(last_modified)) => last_modified.timestamp() == date.timestamp(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn add_headers(&self, headers: &mut Headers) {
+        headers.add(Header {
+            name: String::from("ETag"),
+            value: self.etag.clone(),
+        });
+        if let Some(last_modified) = self.last_modified {
+            headers.add(Header {
+                name: String::from("Last-Modified"),
+                value: last_modified.format(HTTP_DATE_FORMAT).to_string(),
+            });
+        }
+    }
+
+    /// Returns a not modified response, with the validators but without
+    /// body.
+    pub(crate) fn not_modified_response(&self) -> Response {
+        let mut headers = Headers::new();
+        self.add_headers(&mut headers);
+        Response {
+            status: StatusCode::NotModified,
+            headers,
+            body: None,
+        }
+    }
+}
+
+/// Returns a number that identifies the file in its file system, if the
+/// platform has one.
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
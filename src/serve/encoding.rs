@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use log::error;
+use wruster::http::{headers::Header, Body, Request, Response, StatusCode};
+
+use super::{header_value, replace_header, request_header};
+use crate::compression::{self, Encoding, Level, ENCODINGS};
+
+/// Responses smaller than this are not worth compressing.
+const MIN_COMPRESS_LEN: u64 = 1024;
+
+/// Returns the encoding, among the supported ones, preferred by the client
+/// according to the `Accept-Encoding` header of the request.
+pub(crate) fn accepted_encoding(request: &Request) -> Option<Encoding> {
+    let accept_encoding = request_header(request, "Accept-Encoding")?;
+    let accepted: Vec<(&str, f32)> = accept_encoding
+        .split(',')
+        .filter_map(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().filter(|name| !name.is_empty())?;
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map(|quality| quality.parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((name, quality))
+        })
+        .collect();
+    ENCODINGS
+        .iter()
+        .filter_map(|encoding| {
+            // The wildcard only applies to the encodings not listed.
+            let quality = accepted
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(encoding.name()))
+                .or_else(|| accepted.iter().find(|(name, _)| *name == "*"))
+                .map(|(_, quality)| *quality)?;
+            (quality > 0.0).then_some((*encoding, quality))
+        })
+        // Among the encodings with the same quality the preferred one wins.
+        .fold(
+            None,
+            |best: Option<(Encoding, f32)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            },
+        )
+        .map(|(encoding, _)| encoding)
+}
+
+/// Returns the path and the metadata of the version of the file compressed
+/// with the given encoding, e.g.: `style.css.br`, if there is one at least as
+/// recent as the file.
+pub(crate) fn precompressed_file(
+    path: &Path,
+    metadata: &fs::Metadata,
+    encoding: Encoding,
+) -> Option<(PathBuf, fs::Metadata)> {
+    let compressed_path = compression::compressed_path(path, encoding);
+    let compressed_metadata = fs::metadata(&compressed_path).ok()?;
+    let is_fresh = match (compressed_metadata.modified(), metadata.modified()) {
+        (Ok(compressed), Ok(original)) => compressed >= original,
+        _ => false,
+    };
+    match compressed_metadata.is_file() && is_fresh {
+        true => Some((compressed_path, compressed_metadata)),
+        false => None,
+    }
+}
+
+/// Returns the entity tag of the given one for the content compressed with
+/// the encoding, e.g.: `"abc"` becomes `"abc-br"`.
+pub(crate) fn encoded_etag(etag: &str, encoding: Encoding) -> String {
+    format!("{}-{}\"", etag.trim_end_matches('"'), encoding.name())
+}
+
+/// Returns the entity tag without the suffix added by [`encoded_etag`], if
+/// any.
+pub(crate) fn decoded_etag(etag: &str) -> String {
+    let unquoted = etag.trim_end_matches('"');
+    let decoded = ENCODINGS
+        .iter()
+        .find_map(|encoding| unquoted.strip_suffix(&format!("-{}", encoding.name())))
+        .unwrap_or(unquoted);
+    format!("{}\"", decoded)
+}
+
+/// Compresses the body of the response with the given encoding, if the
+/// content is worth compressing and is not already compressed.
+pub(crate) fn compress_response(mut response: Response, encoding: Option<Encoding>) -> Response {
+    if !matches!(response.status, StatusCode::OK) {
+        return response;
+    }
+    let mime_type = match header_value(&response.headers, "Content-Type") {
+        Some(mime_type) if compression::is_compressible(mime_type) => mime_type.to_string(),
+        _ => return response,
+    };
+    // The content served depends on the accepted encodings, so the caches
+    // must take them into account.
+    response.headers.add(Header {
+        name: String::from("Vary"),
+        value: String::from("Accept-Encoding"),
+    });
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None => return response,
+    };
+    if header_value(&response.headers, "Content-Encoding").is_some() {
+        return response;
+    }
+    let content_len = header_value(&response.headers, "Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .unwrap_or_default();
+    if content_len < MIN_COMPRESS_LEN {
+        return response;
+    }
+    let mut body = match response.body.take() {
+        Some(body) => body,
+        None => return response,
+    };
+    let mut content = Vec::new();
+    if let Err(err) = body.content.read_to_end(&mut content) {
+        error!("error reading the content of the response: {}", err);
+        return Response::from_status(StatusCode::InternalServerError);
+    }
+    let mime = mime_type.parse().ok();
+    let compressed = match encoding.compress(&content, Level::Fast) {
+        Ok(compressed) if compressed.len() < content.len() => compressed,
+        Ok(_) => {
+            let len = content.len() as u64;
+            response.body = Some(Body::new(mime, len, Box::new(Cursor::new(content))));
+            return response;
+        }
+        Err(err) => {
+            error!("error compressing the response: {}", err);
+            return Response::from_status(StatusCode::InternalServerError);
+        }
+    };
+    let len = compressed.len() as u64;
+    replace_header(&mut response.headers, "Content-Length", &len.to_string());
+    response.headers.add(Header {
+        name: String::from("Content-Encoding"),
+        value: encoding.name().into(),
+    });
+    if let Some(etag) = header_value(&response.headers, "ETag").map(String::from) {
+        replace_header(
+            &mut response.headers,
+            "ETag",
+            &encoded_etag(&etag, encoding),
+        );
+    }
+    response.body = Some(Body::new(mime, len, Box::new(Cursor::new(compressed))));
+    response
+}
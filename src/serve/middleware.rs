@@ -1,7 +1,16 @@
+use std::sync::Arc;
+
 use log::debug;
-use wruster::http::Request;
+use percent_encoding::percent_decode_str;
+use wruster::http::{headers::Header, Request, Response, StatusCode};
 use wruster::router::HttpHandler;
 
+use super::{
+    cache::{CachePolicies, CacheRoute},
+    encoding, ErrorPages,
+};
+use crate::security_headers::SecurityHeaders;
+
 pub(crate) fn log(handler: HttpHandler) -> HttpHandler {
     Box::new(move |request: &mut Request| {
         debug!("request {:?}", request);
@@ -10,3 +19,89 @@ pub(crate) fn log(handler: HttpHandler) -> HttpHandler {
         response
     })
 }
+
+/// Replaces the uri of the requests with its path, without the query and the
+/// fragment, percent-decoded, so the handlers match and check the path the
+/// client asked for. A path that is not UTF-8 once decoded is a bad request.
+pub(crate) fn decode_path(handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let path = request.uri.split(['?', '#']).next().unwrap_or_default();
+        let path = match percent_decode_str(path).decode_utf8() {
+            Ok(path) => path.into_owned(),
+            Err(err) => {
+                debug!("bad request uri: {}, error: {}", request.uri, err);
+                return Response::from_status(StatusCode::BadRequest);
+            }
+        };
+        request.uri = path;
+        handler(request)
+    })
+}
+
+/// Removes the given prefix from the uri of the requests, so the handlers
+/// serve a blog hosted under a subpath as if it was in the root.
+pub(crate) fn strip_prefix(prefix: String, handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let uri = match request.uri.strip_prefix(prefix.as_str()) {
+            Some("") => Some(String::from("/")),
+            Some(uri) if uri.starts_with('/') => Some(uri.to_string()),
+            _ => None,
+        };
+        if let Some(uri) = uri {
+            request.uri = uri;
+        }
+        handler(request)
+    })
+}
+
+/// Adds the cache policy of the route to the responses of the handler.
+pub(crate) fn cache_control(
+    policies: Arc<CachePolicies>,
+    route: CacheRoute,
+    handler: HttpHandler,
+) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let uri = request.uri.clone();
+        policies.apply(route, &uri, handler(request))
+    })
+}
+
+/// Compresses the responses of the handler with the encoding preferred by
+/// the client, if any.
+pub(crate) fn compress(handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let accepted = encoding::accepted_encoding(request);
+        encoding::compress_response(handler(request), accepted)
+    })
+}
+
+/// Removes the body of the responses of the handler, keeping the headers, so
+/// they are the same a GET request gets. The response is built as for a GET
+/// request to know its length: the pages are rendered, and the compressible
+/// contents compressed, but the other static files are only opened.
+pub(crate) fn head(handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let mut response = handler(request);
+        response.body = None;
+        response
+    })
+}
+
+/// Adds the security headers to all the responses of the handler.
+pub(crate) fn security_headers(headers: Arc<SecurityHeaders>, handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| {
+        let mut response = handler(request);
+        for (name, value) in headers.headers() {
+            response.headers.add(Header {
+                name: name.clone(),
+                value: value.clone(),
+            });
+        }
+        response
+    })
+}
+
+/// Adds the error pages of the blog to the error responses of the handler.
+pub(crate) fn error_pages(pages: Arc<ErrorPages>, handler: HttpHandler) -> HttpHandler {
+    Box::new(move |request: &mut Request| pages.render(handler(request)))
+}
@@ -1,14 +1,23 @@
-use crate::{content, errors::Error, templates, CommandRun};
+use crate::{
+    content,
+    errors::Error,
+    security_headers::{SecurityHeaders, SecurityHeadersArgs},
+    templates,
+    urls::{self, BaseUrl, ASSETS_DIR, DEFAULT_PERMALINK, POST_ASSETS_DIR},
+    CommandRun,
+};
 use clap::{Args, ValueEnum};
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use simplelog::{self, TermLogger};
 use std::{
     fs,
     io::{self, BufReader, Cursor},
     path::{Path, PathBuf},
     process::{self, exit},
-    time::Duration,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
 };
 use wruster::{
     http::{
@@ -19,13 +28,22 @@ use wruster::{
     Server, Timeouts,
 };
 
+mod cache;
+mod conditional;
+mod encoding;
 mod middleware;
+mod range;
+mod static_dir;
+mod tls;
+use cache::{parse_cache_policy, CachePolicies, CacheRoute};
+use conditional::Validators;
+pub(crate) use static_dir::ServeStaticCommand;
+use tls::{Tls, TlsArgs};
 
 const POST_SUBDIR: &str = "posts";
-const ASSETS_SUBDIR: &str = "assets";
-const ASSETS_ROUTE: &str = "/assets";
 const POSTS_ROUTE: &str = "/posts";
-const POST_ASSETS_ROUTE: &str = "/posts/post_assets";
+/// Default seconds to wait for the requests in progress when stopping.
+const DEFAULT_GRACE_PERIOD: u64 = 10;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 pub enum LogLevel {
@@ -57,18 +75,36 @@ pub(crate) struct ServeCommand {
     /// Log level: off, error, info, debug
     #[arg(short, long, value_enum, default_value_t = LogLevel::Info)]
     level: LogLevel,
+    /// Public url of the blog, it replaces the one defined in the blog file.
+    #[arg(long)]
+    base_url: Option<String>,
+    /// Do not serve static files through symlinks.
+    #[arg(long)]
+    no_follow_symlinks: bool,
+    /// Cache-Control header of the responses of a route, it replaces the one
+    /// defined in the blog file. The routes are: home, posts, pages, assets,
+    /// fingerprinted_assets and post_assets.
+    #[arg(long = "cache", value_name = "ROUTE=POLICY", value_parser = parse_cache_policy)]
+    cache: Vec<(String, String)>,
+    /// Send no-cache in all the responses, useful while writing the blog.
+    #[arg(long)]
+    no_cache: bool,
+    #[command(flatten)]
+    security_headers: SecurityHeadersArgs,
+    #[command(flatten)]
+    tls: TlsArgs,
+    /// Seconds to wait for the requests in progress when stopping, if they
+    /// don't finish in time the exit status is 1.
+    #[arg(long, default_value_t = DEFAULT_GRACE_PERIOD)]
+    grace_period: u64,
 }
 
 impl CommandRun for ServeCommand {
     fn run(&self) {
-        TermLogger::init(
-            self.level.into(),
-            simplelog::Config::default(),
-            simplelog::TerminalMode::Mixed,
-            simplelog::ColorChoice::Auto,
-        )
-        .unwrap_or_else(|error| {
-            eprintln!("unexpected error initializing the log level: {}", error);
+        init_log(self.level);
+
+        let tls = self.tls.load().unwrap_or_else(|err| {
+            err.fatal();
             exit(1);
         });
 
@@ -81,7 +117,7 @@ impl CommandRun for ServeCommand {
             });
 
         let templates_assets_path = Path::new(&self.templates)
-            .join(ASSETS_SUBDIR)
+            .join(ASSETS_DIR)
             .canonicalize()
             .unwrap_or_else(|err| {
                 let err =
@@ -98,89 +134,493 @@ impl CommandRun for ServeCommand {
                 exit(1);
             });
 
+        // The routes are under the path of the base url, and the cache
+        // policies are applied when building them, so changes to those in
+        // the blog file require restarting the server.
+        let blog_content =
+            content::read_blog_file(&content_path.to_string_lossy(), self.base_url.as_deref())
+                .unwrap_or_else(|err| {
+                    let err = Error::Undefined(format!("error reading the blog file: {}", err));
+                    err.fatal();
+                    exit(1);
+                });
+        let base_url = blog_content.base_url();
+        let cache_policies = CachePolicies::new(&blog_content.cache, &self.cache, self.no_cache)
+            .unwrap_or_else(|err| {
+                err.fatal();
+                exit(1);
+            });
+        let security_headers =
+            SecurityHeaders::new(&blog_content.security_headers, &self.security_headers);
+
+        // Read the aliases of the posts, as they are served from their own
+        // routes, changes to them require restarting the server.
+        let aliases = read_aliases(&content_path).unwrap_or_else(|err| {
+            let err = Error::Undefined(format!("error reading the aliases of the posts: {}", err));
+            err.fatal();
+            exit(1);
+        });
+
         // Build the router.
-        let router = build_simple_router(templates_assets_path, content_path, blog_templates);
-        // Start the web server.
-        let timeouts = Timeouts {
-            write_response_timeout: Duration::from_secs(5),
-            read_request_timeout: Duration::from_secs(5),
-        };
-        let mut server = Server::from_timeouts(timeouts);
-        server.run(&self.address, router).unwrap_or_else(|err| {
-            error!("running wruster {}", err.to_string());
-            process::exit(1);
+        let router = build_simple_router(
+            templates_assets_path,
+            content_path,
+            blog_templates,
+            aliases,
+            base_url,
+            cache_policies,
+            security_headers,
+            !self.no_follow_symlinks,
+        );
+        let mut servers = Vec::new();
+        if let Some(redirect_address) = self.tls.http_redirect() {
+            let redirect_router = tls::build_redirect_router(&self.address);
+            servers.push(start_server(redirect_address, redirect_router, None));
+        }
+        servers.push(start_server(&self.address, router, tls));
+        run_servers(servers, Duration::from_secs(self.grace_period));
+    }
+}
+
+fn init_log(level: LogLevel) {
+    TermLogger::init(
+        level.into(),
+        simplelog::Config::default(),
+        simplelog::TerminalMode::Mixed,
+        simplelog::ColorChoice::Auto,
+    )
+    .unwrap_or_else(|error| {
+        eprintln!("unexpected error initializing the log level: {}", error);
+        exit(1);
+    });
+}
+
+/// Registers the handler for the GET and the HEAD requests to the route, the
+/// HEAD requests get the same response as the GET ones but without the body.
+fn add_get_and_head(router: &Router, route: &str, handler: HttpHandler) {
+    let handler = Arc::new(handler);
+    let get_handler = handler.clone();
+    let get_handler: HttpHandler = Box::new(move |request: &mut Request| get_handler(request));
+    router.add(route, HttpMethod::GET, get_handler);
+    let head_handler: HttpHandler = Box::new(move |request: &mut Request| handler(request));
+    router.add(route, HttpMethod::HEAD, middleware::head(head_handler));
+}
+
+/// Events that make the servers stop.
+enum StopEvent {
+    /// The process got a SIGINT or a SIGTERM.
+    Signal,
+    /// One of the servers stopped by itself, e.g.: because its listener
+    /// failed, with the error it stopped with.
+    ServerStopped(Result<(), String>),
+}
+
+/// Runs the given servers until the process gets a SIGINT or a SIGTERM, or
+/// until one of them stops by itself. Then it stops all of them at the same
+/// time, waiting up to the grace period for the requests in progress to
+/// finish, and exits. The exit status is 0 only if the stop was requested by
+/// a signal and all the requests in progress finished in the grace period,
+/// otherwise it's 1.
+fn run_servers(servers: Vec<Server>, grace_period: Duration) -> ! {
+    let servers: Vec<Arc<Server>> = servers.into_iter().map(Arc::new).collect();
+    let (event_sender, event_receiver) = mpsc::channel();
+    let signal_sender = event_sender.clone();
+    ctrlc::set_handler(move || {
+        let _ = signal_sender.send(StopEvent::Signal);
+    })
+    .unwrap_or_else(|err| {
+        error!("error setting the signal handler: {}", err);
+        process::exit(1);
+    });
+    // The servers run in their own threads, and only stop by themselves if
+    // something fails.
+    for server in &servers {
+        let server = server.clone();
+        let event_sender = event_sender.clone();
+        thread::spawn(move || {
+            let result = server.wait().map_err(|err| err.to_string());
+            let _ = event_sender.send(StopEvent::ServerStopped(result));
         });
-        server.wait().unwrap_or_else(|err| {
-            error!("error running wruster {}", err.to_string());
-            process::exit(1);
+    }
+    let mut status = match event_receiver.recv() {
+        Ok(StopEvent::Signal) => 0,
+        Ok(StopEvent::ServerStopped(Ok(()))) => {
+            error!("wruster stopped unexpectedly");
+            1
+        }
+        Ok(StopEvent::ServerStopped(Err(err))) => {
+            error!("error running wruster {}", err);
+            1
+        }
+        Err(_) => {
+            error!("error waiting for the signals");
+            1
+        }
+    };
+    info!(
+        "shutting down, waiting up to {:?} for the requests in progress",
+        grace_period
+    );
+    let (stopped_sender, stopped_receiver) = mpsc::channel();
+    for server in &servers {
+        let server = server.clone();
+        let stopped_sender = stopped_sender.clone();
+        thread::spawn(move || {
+            let result = server.shutdown();
+            if let Err(err) = &result {
+                error!("error shutting down wruster {}", err.to_string());
+            }
+            let _ = stopped_sender.send(result.is_ok());
         });
-        process::exit(0);
     }
+    let deadline = Instant::now() + grace_period;
+    for _ in &servers {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match stopped_receiver.recv_timeout(remaining) {
+            Ok(true) => (),
+            Ok(false) => status = 1,
+            Err(_) => {
+                error!("the requests in progress didn't finish in the grace period");
+                status = 1;
+                break;
+            }
+        }
+    }
+    if status == 0 {
+        info!("server stopped");
+    }
+    log::logger().flush();
+    process::exit(status);
+}
+
+/// Starts serving the given router in the address, through https if `tls` is
+/// given, and returns the running server.
+fn start_server(address: &str, router: Router, tls: Option<Tls>) -> Server {
+    let timeouts = Timeouts {
+        write_response_timeout: Duration::from_secs(5),
+        read_request_timeout: Duration::from_secs(5),
+    };
+    let mut server = Server::from_timeouts(timeouts);
+    let result = match tls {
+        Some(tls) => run_tls(&mut server, address, router, tls),
+        None => server.run(address, router).map_err(|err| err.to_string()),
+    };
+    result.unwrap_or_else(|err| {
+        error!("running wruster {}", err);
+        process::exit(1);
+    });
+    server
+}
+
+/// Starts serving the given router through https in the address. wruster
+/// only configures TLS with a single certificate, so it serves plain http in
+/// a local port, and a proxy in the address decrypts the connections and
+/// forwards them to it, sending the whole chain to the clients.
+fn run_tls(server: &mut Server, address: &str, router: Router, tls: Tls) -> Result<(), String> {
+    let local_address = tls::local_address().map_err(|err| err.to_string())?;
+    // The proxy listens first, so it doesn't miss the connections made as
+    // soon as the server is listening.
+    let proxy = tls::Proxy::bind(address, tls).map_err(|err| err.to_string())?;
+    server
+        .run(&local_address.to_string(), router)
+        .map_err(|err| err.to_string())?;
+    debug!("serving https in {} from {}", address, local_address);
+    proxy.run(local_address);
+    Ok(())
 }
 
 fn build_simple_router(
     template_assets_dir: PathBuf,
     content_dir: PathBuf,
     blog_templates: templates::Blog,
+    aliases: Vec<(String, String)>,
+    base_url: BaseUrl,
+    cache_policies: CachePolicies,
+    security_headers: SecurityHeaders,
+    follow_symlinks: bool,
 ) -> Router {
+    let cache_policies = Arc::new(cache_policies);
+    let security_headers = Arc::new(security_headers);
     let router = Router::new();
+    let (main_template, post_template, page_template, error_templates) = blog_templates.parts();
+    let error_pages = Arc::new(ErrorPages {
+        templates: error_templates,
+        content_dir: content_dir.clone(),
+        base_url: base_url.clone(),
+    });
+    // All the routes are under the path of the base url, use the error pages
+    // of the blog and add the security headers.
+    let add_route = |route: &str, handler: HttpHandler| {
+        let base_route = match (route, base_url.path()) {
+            (urls::ROOT_URL, "") => route.to_string(),
+            (urls::ROOT_URL, base_path) => base_path.to_string(),
+            (route, _) => base_url.link(route),
+        };
+        let handler = middleware::strip_prefix(base_url.path().into(), handler);
+        let handler = middleware::decode_path(handler);
+        let handler = middleware::error_pages(error_pages.clone(), handler);
+        let handler = middleware::compress(handler);
+        let handler = middleware::security_headers(security_headers.clone(), handler);
+        add_get_and_head(&router, &base_route, handler);
+    };
     // Handler for the static assets of the templates.
     debug!(
         "serving template assets from dir: {}",
         template_assets_dir.to_string_lossy()
     );
-    let (main_template, post_template) = blog_templates.parts();
+    let post_template = Arc::new(post_template);
 
-    // index route: /
+    // index route: /, it also serves the standalone pages, e.g.: /about, and
+    // the posts with a permalink outside the posts route.
     let main_handler_content_dir = content_dir.clone();
+    let main_handler_post_template = post_template.clone();
+    let main_handler_base_url = base_url.clone();
+    let main_handler_cache_policies = cache_policies.clone();
     let main_handler = move |request: &mut Request| -> Response {
-        serve_main_page(main_handler_content_dir.clone(), request, &main_template)
+        let uri = request.uri.clone();
+        let (route, response) = match uri.as_str() {
+            "/" | "" => (
+                CacheRoute::Home,
+                serve_main_page(
+                    main_handler_content_dir.clone(),
+                    request,
+                    &main_template,
+                    &main_handler_base_url,
+                ),
+            ),
+            uri if page_file_path(&main_handler_content_dir, uri).is_some() => (
+                CacheRoute::Pages,
+                serve_page(
+                    main_handler_content_dir.clone(),
+                    request,
+                    page_template.as_ref(),
+                    &main_handler_base_url,
+                ),
+            ),
+            _ => (
+                CacheRoute::Posts,
+                serve_post(
+                    main_handler_content_dir.clone(),
+                    request,
+                    &main_handler_post_template,
+                    &main_handler_base_url,
+                ),
+            ),
+        };
+        main_handler_cache_policies.apply(route, &uri, response)
     };
     let main_handler: HttpHandler = Box::new(main_handler);
-    router.add("/", HttpMethod::GET, main_handler);
+    add_route(urls::ROOT_URL, main_handler);
 
     // commmon assets route: assets/*
+    let assets_route = format!("/{}", ASSETS_DIR);
+    let assets_handler_route = assets_route.clone();
     let assets_handler = move |request: &mut Request| -> Response {
         serve_static(
-            ASSETS_ROUTE.into(),
+            assets_handler_route.clone(),
             template_assets_dir.clone(),
             request,
             Some(vec!["md"]),
+            follow_symlinks,
         )
     };
-    let assets_handler: HttpHandler = Box::new(assets_handler);
-    router.add(ASSETS_ROUTE, HttpMethod::GET, assets_handler);
+    let assets_handler = middleware::cache_control(
+        cache_policies.clone(),
+        CacheRoute::Assets,
+        Box::new(assets_handler),
+    );
+    add_route(&assets_route, assets_handler);
 
-    // post article route: /posts/article.md
+    // post article route: /posts/article
     let post_handler_content_dir = content_dir.clone();
+    let post_handler_base_url = base_url.clone();
     let posts_handler = move |request: &mut Request| -> Response {
-        serve_post(post_handler_content_dir.clone(), request, &post_template)
+        serve_post(
+            post_handler_content_dir.clone(),
+            request,
+            &post_template,
+            &post_handler_base_url,
+        )
     };
-    let posts_handler: HttpHandler = middleware::log(Box::new(posts_handler));
-    router.add(POSTS_ROUTE, HttpMethod::GET, posts_handler);
+    let posts_handler = middleware::cache_control(
+        cache_policies.clone(),
+        CacheRoute::Posts,
+        Box::new(posts_handler),
+    );
+    let posts_handler: HttpHandler = middleware::log(posts_handler);
+    add_route(POSTS_ROUTE, posts_handler);
 
-    // post assets route: /posts/article_asset
+    // post assets route: /post_assets/article_asset
+    let post_assets_route = format!("/{}", POST_ASSETS_DIR);
+    let post_assets_handler_route = post_assets_route.clone();
     let post_asssets_dir = content_dir.join(POST_SUBDIR);
     let posts_assets_handler = move |request: &mut Request| -> Response {
         serve_static(
-            POST_ASSETS_ROUTE.into(),
+            post_assets_handler_route.clone(),
             post_asssets_dir.clone(),
             request,
             Some(vec!["md"]),
+            follow_symlinks,
         )
     };
-    let posts_assets_handler: HttpHandler = Box::new(posts_assets_handler);
-    router.add(POST_ASSETS_ROUTE, HttpMethod::GET, posts_assets_handler);
+    let posts_assets_handler = middleware::cache_control(
+        cache_policies,
+        CacheRoute::PostAssets,
+        Box::new(posts_assets_handler),
+    );
+    add_route(&post_assets_route, posts_assets_handler);
+
+    // post aliases routes: /old-post-url
+    for (alias, post_url) in aliases {
+        debug!("redirecting alias {} to {}", alias, post_url);
+        let route = alias.clone();
+        let post_url = base_url.link(&post_url);
+        let alias_handler =
+            move |request: &mut Request| -> Response { serve_alias(&alias, &post_url, request) };
+        let alias_handler: HttpHandler = Box::new(alias_handler);
+        add_route(&route, alias_handler);
+    }
 
     router
 }
 
+/// Renders the error pages of the blog for the requests that fail.
+pub(crate) struct ErrorPages {
+    templates: templates::ErrorPages,
+    content_dir: PathBuf,
+    base_url: BaseUrl,
+}
+
+impl ErrorPages {
+    /// Adds the error page to the not found and internal server error
+    /// responses without body, if the blog has a template for it.
+    pub(crate) fn render(&self, response: Response) -> Response {
+        if response.body.is_some() {
+            return response;
+        }
+        let (status, status_code) = match response.status {
+            StatusCode::NotFound => (StatusCode::NotFound, 404),
+            StatusCode::InternalServerError => (StatusCode::InternalServerError, 500),
+            _ => return response,
+        };
+        let content_dir = self.content_dir.to_string_lossy();
+        let blog_content = match content::read_blog_file(&content_dir, Some(self.base_url.url())) {
+            Ok(blog_content) => blog_content,
+            Err(err) => {
+                error!("error reading the blog file for the error page: {}", err);
+                return response;
+            }
+        };
+        let model = templates::ErrorTemplateModel {
+            status: status_code,
+            root_page: self.base_url.link(urls::ROOT_URL),
+            site: templates::SiteTemplateModel::from(&blog_content),
+        };
+        let content = match status_code {
+            404 => self.templates.render_not_found(&model),
+            _ => self.templates.render_server_error(&model),
+        };
+        let content = match content {
+            Some(content) => content,
+            None => return response,
+        };
+        let content_len = content.len() as u64;
+        let content = Cursor::new(content);
+        let mut error_response = Response::from_content(content, content_len, mime::TEXT_HTML);
+        error_response.status = status;
+        error_response
+    }
+}
+
+/// Returns the aliases of the posts together with the url of the post they
+/// redirect to.
+fn read_aliases(content_dir: &Path) -> Result<Vec<(String, String)>, Error> {
+    // Reading the whole blog ensures the aliases are the same ones the pack
+    // command accepts.
+    let blog_content =
+        content::Blog::read_from(&content_dir.to_string_lossy(), DEFAULT_PERMALINK, None)?;
+    let aliases = blog_content
+        .posts
+        .into_iter()
+        .flat_map(|post| {
+            let url = post.metadata.url;
+            post.metadata
+                .aliases
+                .into_iter()
+                .map(move |alias| (alias, url.clone()))
+        })
+        .collect();
+    Ok(aliases)
+}
+
+pub fn serve_alias(alias: &str, post_url: &str, request: &Request) -> Response {
+    // The router also sends to this handler the requests to paths under the
+    // alias, those are not redirected.
+    if request.uri.trim_end_matches('/') != alias.trim_end_matches('/') {
+        return Response::from_status(StatusCode::NotFound);
+    }
+    debug!("redirecting alias {} to {}", alias, post_url);
+    redirect(post_url)
+}
+
+/// Returns a response with the given html content and its validators, or a
+/// not modified response if the client already has the content.
+fn html_response(request: &Request, content: String) -> Response {
+    let validators = Validators::from_content(&content);
+    if validators.not_modified(request) {
+        return validators.not_modified_response();
+    }
+    let content_len = content.len() as u64;
+    let content = Cursor::new(content);
+    let mut response = Response::from_content(content, content_len, mime::TEXT_HTML);
+    validators.add_headers(&mut response.headers);
+    response
+}
+
+/// Returns the value of the given header of the request, if present.
+fn request_header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    header_value(&request.headers, name)
+}
+
+/// Returns the value of the given header, if present.
+fn header_value<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers.get(name).map(|header| header.value.as_str())
+}
+
+/// Sets the value of the given header, replacing the current one, if any.
+fn replace_header(headers: &mut Headers, name: &str, value: &str) {
+    headers.remove(name);
+    headers.add(Header {
+        name: name.into(),
+        value: value.into(),
+    });
+}
+
+/// Returns a response that permanently redirects to the given location.
+fn redirect(location: &str) -> Response {
+    let mut headers = Headers::new();
+    headers.add(Header {
+        name: String::from("Location"),
+        value: location.into(),
+    });
+    headers.add(Header {
+        name: String::from("Content-Length"),
+        value: String::from("0"),
+    });
+    Response {
+        status: StatusCode::MovedPermanently,
+        headers,
+        body: None,
+    }
+}
+
 pub fn serve_post(
     content_dir: PathBuf,
     request: &Request,
     templates: &templates::Post,
+    base_url: &BaseUrl,
 ) -> Response {
-    let mut uri = PathBuf::from(request.uri.as_str());
+    let uri = PathBuf::from(request.uri.as_str());
     if uri.extension().unwrap_or_default() == "md" {
         debug!(
             "handle_blog_request: discarding request to .md file: {}",
@@ -189,29 +629,15 @@ pub fn serve_post(
         return Response::from_status(StatusCode::NotFound);
     }
     debug!("serving content, raw request uri: {}", uri.display());
-    // Remove the route from the path.
-    uri = match uri.strip_prefix::<PathBuf>(POSTS_ROUTE.into()) {
-        Ok(uri) => uri.to_path_buf(),
-        Err(err) => {
-            debug!("serving content, bad request, error: {}", err.to_string());
-            return Response::from_status(StatusCode::BadRequest);
+    match generate_post_content(templates, &content_dir, request.uri.as_str(), base_url) {
+        Ok(Some(content)) => html_response(request, content),
+        Ok(None) => {
+            debug!("serving content, no post found for: {}", uri.display());
+            Response::from_status(StatusCode::NotFound)
         }
-    };
-    debug!(
-        "serving content for a post, uri: {}, file name: {}",
-        uri.display(),
-        uri.file_name().unwrap_or_default().to_string_lossy(),
-    );
-    let post_file = uri
-        .file_name()
-        .unwrap_or_default()
-        .to_str()
-        .unwrap_or_default();
-    match generate_post_content(templates, &content_dir, post_file) {
-        Ok(content) => {
-            let content_len = content.len() as u64;
-            let content = Cursor::new(content);
-            Response::from_content(content, content_len, mime::TEXT_HTML)
+        Err(Error::NotFound(err)) => {
+            debug!("serving content, post file not found: {}", err);
+            Response::from_status(StatusCode::NotFound)
         }
         Err(err) => {
             error!("serving content error generating post content: {}", err);
@@ -224,16 +650,13 @@ pub fn serve_main_page(
     content_dir: PathBuf,
     request: &Request,
     templates: &templates::Main,
+    base_url: &BaseUrl,
 ) -> Response {
     info!("serving content, raw request uri: {}", request.uri);
     let uri = request.uri.as_str();
     match uri {
-        "/" | "" => match generate_main_page_content(templates, &content_dir) {
-            Ok(content) => {
-                let content_len = content.len() as u64;
-                let content = Cursor::new(content);
-                Response::from_content(content, content_len, mime::TEXT_HTML)
-            }
+        "/" | "" => match generate_main_page_content(templates, &content_dir, base_url) {
+            Ok(content) => html_response(request, content),
             Err(err) => {
                 error!(
                     "serving content error generating main page content: {}",
@@ -246,29 +669,120 @@ pub fn serve_main_page(
     }
 }
 
+pub fn serve_page(
+    content_dir: PathBuf,
+    request: &Request,
+    templates: Option<&templates::Page>,
+    base_url: &BaseUrl,
+) -> Response {
+    let templates = match templates {
+        Some(templates) => templates,
+        None => {
+            warn!("no page template found, not serving page: {}", request.uri);
+            return Response::from_status(StatusCode::NotFound);
+        }
+    };
+    debug!("serving page, raw request uri: {}", request.uri);
+    let page_file_path = match page_file_path(&content_dir, &request.uri) {
+        Some(page_file_path) => page_file_path,
+        None => return Response::from_status(StatusCode::NotFound),
+    };
+    match generate_page_content(templates, &content_dir, &page_file_path, base_url) {
+        Ok(content) => html_response(request, content),
+        Err(Error::NotFound(err)) => {
+            debug!("serving page, page file not found: {}", err);
+            Response::from_status(StatusCode::NotFound)
+        }
+        Err(err) => {
+            error!("serving content error generating page content: {}", err);
+            Response::from_status(StatusCode::InternalServerError)
+        }
+    }
+}
+
+/// Returns the path of the file of the page for the given uri, if there is
+/// one. The slug of a page can be defined in its front matter, so the front
+/// matter of every page is read to find it.
+fn page_file_path(content_dir: &Path, uri: &str) -> Option<PathBuf> {
+    let slug = uri.trim_matches('/');
+    // Pages are published in the root.
+    if slug.is_empty() || slug.contains('/') {
+        return None;
+    }
+    let page_files = match content::page_files(content_dir) {
+        Ok(page_files) => page_files,
+        Err(err) => {
+            error!("error reading the pages: {}", err);
+            return None;
+        }
+    };
+    page_files
+        .into_iter()
+        .find(|page_file| match content::read_page_slug(page_file) {
+            Ok(page_slug) => page_slug == slug,
+            Err(err) => {
+                debug!("error reading the slug of {}: {}", page_file.display(), err);
+                false
+            }
+        })
+}
+
+fn generate_page_content(
+    templates: &templates::Page,
+    content_dir: &Path,
+    page_file_path: &Path,
+    base_url: &BaseUrl,
+) -> Result<String, Error> {
+    let page = content::read_page_file(&page_file_path.to_string_lossy(), base_url)?;
+    let blog_content =
+        content::read_blog_file(&content_dir.to_string_lossy(), Some(base_url.url()))?;
+    let page_model = templates::PageTemplateModel {
+        title: page.title,
+        root_page: base_url.link(urls::ROOT_URL),
+        content: page.content,
+        canonical_url: base_url.absolute(&urls::page_url(&page.slug)),
+        slug: page.slug,
+        site: templates::SiteTemplateModel::from(&blog_content),
+        extra: templates::Extra::from(&page.extra),
+    };
+    Ok(templates.render(&page_model))
+}
+
+/// Generates the content of the post published under the given url, returns
+/// None if there is no such post.
 fn generate_post_content(
     templates: &templates::Post,
     post_content_dir: &PathBuf,
-    post_file: &str,
-) -> Result<String, Error> {
-    let post_file_path = Path::new(post_content_dir)
-        .join(POST_SUBDIR)
-        .join(post_file);
-    let post_file_path = match post_file_path.to_str() {
-        Some(file_path) => file_path,
-        None => return Err(Error::Undefined("invalid path".into())),
+    url: &str,
+    base_url: &BaseUrl,
+) -> Result<Option<String>, Error> {
+    let blog_content =
+        content::read_blog_file(&post_content_dir.to_string_lossy(), Some(base_url.url()))?;
+    let permalink = blog_content
+        .permalink
+        .as_deref()
+        .unwrap_or(DEFAULT_PERMALINK);
+    let posts_dir = Path::new(post_content_dir).join(POST_SUBDIR);
+    let posts_dir = posts_dir.to_string_lossy();
+    let post_file = content::find_post_file(&posts_dir, permalink, url)?;
+    let (post_file_path, post_assets_dir) = match post_file {
+        Some(post_file) => post_file,
+        None => return Ok(None),
     };
-    let post_file_path = format!("{}.md", post_file_path);
-    debug!("generating post content from file: {}", post_file_path);
-    let post = content::read_post_file(&post_file_path)?;
+    debug!(
+        "generating post content from file: {}",
+        post_file_path.display()
+    );
+    let post = content::read_post_file(&post_file_path, &post_assets_dir, permalink, base_url)?;
     let post_model = templates::PostTemplateModel {
         author: post.author,
         title: post.title,
-        root_page: "/".into(),
+        root_page: base_url.link(urls::ROOT_URL),
         content: post.content,
         date: templates::DateTime(post.date.0),
         favorite: post.favorite,
-        file_name: post_file.into(),
+        file_name: base_url.link(&post.url),
+        canonical_url: base_url.absolute(&post.url),
         summary: post.summary,
         tags: post
             .tags
@@ -276,44 +790,51 @@ fn generate_post_content(
             .map(|tag| templates::Tag(tag.0.clone()))
             .collect(),
         year: post.year,
+        site: templates::SiteTemplateModel::from(&blog_content),
+        extra: templates::Extra::from(&post.extra),
     };
-    Ok(templates.render(&post_model))
+    Ok(Some(templates.render(&post_model)))
 }
 
 fn generate_main_page_content(
     templates: &templates::Main,
     content_dir: &Path,
+    base_url: &BaseUrl,
 ) -> Result<String, Error> {
     let posts_dir = content_dir.join(POST_SUBDIR);
     let posts_dir = posts_dir.to_string_lossy();
 
     let content_dir = content_dir.to_string_lossy();
 
-    let blog_content = content::read_blog_file(&content_dir)?;
-    let mut posts_metadata = content::read_posts_metadata(&posts_dir)?;
+    let blog_content = content::read_blog_file(&content_dir, Some(base_url.url()))?;
+    let site = templates::SiteTemplateModel::from(&blog_content);
+    let permalink = blog_content
+        .permalink
+        .as_deref()
+        .unwrap_or(DEFAULT_PERMALINK);
+    let mut posts_metadata = content::read_posts_metadata(&posts_dir, permalink)?;
     // Sort the blog posts by date.
     posts_metadata.sort_by(|a, b| b.date.cmp(&a.date));
     let posts_template_models = posts_metadata
         .into_iter()
-        .map(|metadata| {
-            let mut file_name = metadata.file_name.replace(".md", "");
-            file_name = format!("{}/{}", POSTS_ROUTE, file_name);
-            templates::PostTemplateModel {
-                author: metadata.author,
-                title: metadata.title,
-                content: "".into(),
-                date: templates::DateTime(metadata.date.0),
-                file_name,
-                root_page: "/".into(),
-                summary: metadata.summary,
-                tags: metadata
-                    .tags
-                    .iter()
-                    .map(|tag| templates::Tag(tag.0.clone()))
-                    .collect(),
-                favorite: false,
-                year: "".into(),
-            }
+        .map(|metadata| templates::PostTemplateModel {
+            author: metadata.author,
+            title: metadata.title,
+            content: "".into(),
+            date: templates::DateTime(metadata.date.0),
+            file_name: base_url.link(&metadata.url),
+            canonical_url: base_url.absolute(&metadata.url),
+            root_page: base_url.link(urls::ROOT_URL),
+            summary: metadata.summary,
+            tags: metadata
+                .tags
+                .iter()
+                .map(|tag| templates::Tag(tag.0.clone()))
+                .collect(),
+            favorite: false,
+            year: "".into(),
+            site: site.clone(),
+            extra: templates::Extra::from(&metadata.extra),
         })
         .collect();
 
@@ -324,6 +845,9 @@ fn generate_main_page_content(
         twitter: blog_content.twitter,
         year: blog_content.year,
         posts: posts_template_models,
+        canonical_url: base_url.absolute(urls::ROOT_URL),
+        site,
+        extra: templates::Extra::from(&blog_content.extra),
     };
     Ok(templates.render(&main_template_model))
 }
@@ -333,38 +857,21 @@ pub fn serve_static(
     base_dir: PathBuf,
     request: &Request,
     exclude_extensions: Option<Vec<&str>>,
+    follow_symlinks: bool,
 ) -> Response {
     debug!(
         "serving static from base dir: {}",
         base_dir.to_str().unwrap()
     );
-    let mut uri = PathBuf::from(request.uri.as_str());
+    let path = match static_file_path(&route, &base_dir, &request.uri, follow_symlinks) {
+        Ok(path) => path,
+        Err(status) => return Response::from_status(status),
+    };
     if let Some(exclude) = exclude_extensions {
-        if uri.has_any_extension(exclude) {
+        if path.has_any_extension(exclude) {
             return Response::from_status(StatusCode::NotFound);
         }
     }
-    // Remove the route from the path.
-    if uri.starts_with(route.as_str()) {
-        uri = match uri.strip_prefix::<PathBuf>(route.into()) {
-            Ok(uri) => uri.to_path_buf(),
-            Err(err) => {
-                debug!("serving static bad request, error: {}", err.to_string());
-                return Response::from_status(StatusCode::BadRequest);
-            }
-        }
-    }
-    // Do not allow serving the root of the route.
-    let mut uri = uri.to_str().unwrap();
-    if uri.starts_with('/') {
-        if uri.len() < 2 {
-            return Response::from_status(StatusCode::NotFound);
-        }
-        uri = &uri[1..]
-    }
-    // Append the path minus the route to the base directory.
-    let mut path = base_dir;
-    path.push(uri);
     debug!(
         "serving static resource from path: {}",
         path.to_str().unwrap()
@@ -378,6 +885,64 @@ pub fn serve_static(
             return Response::from_status(StatusCode::InternalServerError);
         }
     };
+    if !metadata.is_file() {
+        return Response::from_status(StatusCode::NotFound);
+    }
+    let mime_type = mime_guess::from_path(&path).first_or_octet_stream();
+    // Serve the compressed version of the file, if the client accepts it and
+    // the pack command generated it.
+    let precompressed = encoding::accepted_encoding(request).and_then(|encoding| {
+        encoding::precompressed_file(&path, &metadata, encoding)
+            .map(|(path, metadata)| (encoding, path, metadata))
+    });
+    // The validators are the ones of the file, the compressed versions are
+    // generated from it, with the entity tag of the version served.
+    let validators = Validators::from_file(&metadata);
+    let (encoding, validators, path, metadata) = match precompressed {
+        Some((encoding, path, metadata)) => {
+            let validators = validators.encoded(encoding);
+            (Some(encoding), validators, path, metadata)
+        }
+        None => (None, validators, path, metadata),
+    };
+    if validators.not_modified(request) {
+        return validators.not_modified_response();
+    }
+
+    let mut headers = Headers::new();
+    headers.add(Header {
+        name: String::from("Accept-Ranges"),
+        value: String::from("bytes"),
+    });
+    validators.add_headers(&mut headers);
+    if let Some(encoding) = encoding {
+        headers.add(Header {
+            name: String::from("Content-Encoding"),
+            value: encoding.name().into(),
+        });
+    }
+
+    // Ranges are ignored if the content changed since the client got part
+    // of it.
+    let ranges = match request_header(request, "Range") {
+        Some(range) if validators.if_range_matches(request) => {
+            range::parse_ranges(range, metadata.len())
+        }
+        _ => None,
+    };
+    match ranges {
+        Some(ranges) if ranges.is_empty() => {
+            return range::not_satisfiable_response(metadata.len());
+        }
+        Some(ranges) => {
+            return range::partial_response(&path, &mime_type, metadata.len(), &ranges, headers)
+                .unwrap_or_else(|err| {
+                    error!("serving static, error reading {}: {}", path.display(), err);
+                    Response::from_status(StatusCode::InternalServerError)
+                });
+        }
+        None => (),
+    }
 
     let content = match fs::File::open(&path) {
         Ok(content) => content,
@@ -388,8 +953,6 @@ pub fn serve_static(
             return Response::from_status(StatusCode::InternalServerError);
         }
     };
-    let mime_type = mime_guess::from_path(path).first_or_octet_stream();
-    let mut headers = Headers::new();
     let content = Box::new(BufReader::new(content));
     headers.add(Header {
         name: String::from("Content-Length"),
@@ -407,6 +970,59 @@ pub fn serve_static(
     }
 }
 
+/// Returns the canonical path of the file requested by the given uri, served
+/// by the given route from the `base_dir`. It ensures the file lies under the
+/// `base_dir`, and that neither the file nor any of its parents is hidden or,
+/// if `follow_symlinks` is false, a symlink. The uri must be already decoded,
+/// see [`middleware::decode_path`], so encoded segments, e.g.: %2e%2e, are
+/// checked too. On error, it returns the status of the response.
+fn static_file_path(
+    route: &str,
+    base_dir: &Path,
+    uri: &str,
+    follow_symlinks: bool,
+) -> Result<PathBuf, StatusCode> {
+    // Remove the route from the path.
+    let uri = match uri.strip_prefix(route.trim_end_matches('/')) {
+        Some(uri) if uri.is_empty() || uri.starts_with('/') => uri,
+        _ => return Err(StatusCode::NotFound),
+    };
+    let mut path = base_dir.to_path_buf();
+    for segment in uri.split('/').filter(|segment| !segment.is_empty()) {
+        // Parent segments, hidden files and segments that could be
+        // interpreted as more than one path component are never served.
+        if segment.starts_with('.') || segment.contains(['\\', '\0']) {
+            debug!("serving static, refusing to serve uri: {}", uri);
+            return Err(StatusCode::NotFound);
+        }
+        path.push(segment);
+        if !follow_symlinks && path.is_symlink() {
+            debug!("serving static, refusing to follow symlink: {}", uri);
+            return Err(StatusCode::NotFound);
+        }
+    }
+    // Do not allow serving the root of the route.
+    if path == base_dir {
+        return Err(StatusCode::NotFound);
+    }
+    let canonical_path = path.canonicalize().map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => StatusCode::NotFound,
+        _ => StatusCode::InternalServerError,
+    })?;
+    let canonical_base_dir = base_dir
+        .canonicalize()
+        .map_err(|_| StatusCode::InternalServerError)?;
+    // The symlinks can point outside the base directory.
+    if !canonical_path.starts_with(&canonical_base_dir) {
+        debug!(
+            "serving static, refusing to serve file outside the base dir: {}",
+            uri
+        );
+        return Err(StatusCode::NotFound);
+    }
+    Ok(canonical_path)
+}
+
 trait HasAnyExtension
 where
     Self: std::marker::Sized,
@@ -0,0 +1,141 @@
+use std::{
+    fs,
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+};
+
+use wruster::http::{
+    headers::{Header, Headers},
+    Body, Response, StatusCode,
+};
+
+/// Requests with more ranges than this are served completely, as a client
+/// asking for that many parts is probably misbehaving.
+const MAX_RANGES: usize = 16;
+
+const MULTIPART_BOUNDARY: &str = "wrustblog-byteranges";
+
+/// Returns the satisfiable byte ranges of the `Range` header for a content of
+/// the given length. Returns None if the header is not a valid byte ranges
+/// header, in which case it must be ignored, and an empty list if none of the
+/// ranges can be satisfied.
+pub(crate) fn parse_ranges(header: &str, len: u64) -> Option<Vec<Range<u64>>> {
+    let specs = header.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        let (start, end) = spec.split_once('-')?;
+        let range = match (start.trim(), end.trim()) {
+            // The last bytes of the content, e.g.: -500.
+            ("", suffix_len) => {
+                let suffix_len: u64 = suffix_len.parse().ok()?;
+                len.saturating_sub(suffix_len)..len
+            }
+            // From a position to the end, e.g.: 500-.
+            (start, "") => start.parse().ok()?..len,
+            (start, end) => {
+                let start: u64 = start.parse().ok()?;
+                let end: u64 = end.parse().ok()?;
+                if end < start {
+                    return None;
+                }
+                start..len.min(end.saturating_add(1))
+            }
+        };
+        if range.start < range.end {
+            ranges.push(range);
+        }
+    }
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(ranges)
+}
+
+/// Returns a partial content response with the given ranges of the file, a
+/// multipart one if there is more than one range. The `headers` are added to
+/// the response.
+pub(crate) fn partial_response(
+    path: &Path,
+    mime_type: &mime::Mime,
+    len: u64,
+    ranges: &[Range<u64>],
+    mut headers: Headers,
+) -> io::Result<Response> {
+    let (content, content_len, content_type): (Box<dyn Read + Send>, u64, String) = match ranges {
+        [range] => {
+            headers.add(Header {
+                name: String::from("Content-Range"),
+                value: content_range(range, len),
+            });
+            let content = read_range(path, range)?;
+            (content, range.end - range.start, mime_type.to_string())
+        }
+        ranges => {
+            let mut content: Box<dyn Read + Send> = Box::new(io::empty());
+            let mut content_len = 0;
+            for range in ranges {
+                let part_headers = format!(
+                    "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                    MULTIPART_BOUNDARY,
+                    mime_type,
+                    content_range(range, len)
+                );
+                content_len += part_headers.len() as u64 + range.end - range.start;
+                content = Box::new(
+                    content
+                        .chain(Cursor::new(part_headers))
+                        .chain(read_range(path, range)?),
+                );
+            }
+            let end = format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY);
+            content_len += end.len() as u64;
+            content = Box::new(content.chain(Cursor::new(end)));
+            let content_type = format!("multipart/byteranges; boundary={}", MULTIPART_BOUNDARY);
+            (content, content_len, content_type)
+        }
+    };
+    headers.add(Header {
+        name: String::from("Content-Length"),
+        value: content_len.to_string(),
+    });
+    headers.add(Header {
+        name: String::from("Content-Type"),
+        value: content_type.clone(),
+    });
+    let body = Body::new(content_type.parse().ok(), content_len, content);
+    Ok(Response {
+        status: StatusCode::PartialContent,
+        headers,
+        body: Some(body),
+    })
+}
+
+/// Returns the response for a request with none of its ranges inside the
+/// content of the given length.
+pub(crate) fn not_satisfiable_response(len: u64) -> Response {
+    let mut headers = Headers::new();
+    headers.add(Header {
+        name: String::from("Content-Range"),
+        value: format!("bytes */{}", len),
+    });
+    headers.add(Header {
+        name: String::from("Content-Length"),
+        value: String::from("0"),
+    });
+    Response {
+        status: StatusCode::RangeNotSatisfiable,
+        headers,
+        body: None,
+    }
+}
+
+fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, len)
+}
+
+fn read_range(path: &Path, range: &Range<u64>) -> io::Result<Box<dyn Read + Send>> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    Ok(Box::new(BufReader::new(file.take(range.end - range.start))))
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::exit,
+    time::Duration,
+};
+
+use clap::Args;
+use log::debug;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use wruster::{
+    http::{Request, Response, StatusCode},
+    router::Router,
+};
+
+use super::{
+    add_get_and_head, init_log, middleware, redirect, run_servers, serve_static, start_server,
+    LogLevel, DEFAULT_GRACE_PERIOD,
+};
+use crate::{errors::Error, urls::INDEX_FILE, CommandRun};
+
+const NOT_FOUND_FILE: &str = "404.html";
+
+/// Characters encoded in the path of the redirects, the uri of the request is
+/// decoded.
+const LOCATION_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Args, Debug)]
+pub(crate) struct ServeStaticCommand {
+    /// Path to a directory generated by the pack command.
+    dir: String,
+    /// Address to listen to, for example: localhost:8080
+    address: String,
+    /// Log level: off, error, info, debug
+    #[arg(short, long, value_enum, default_value_t = LogLevel::Info)]
+    level: LogLevel,
+    /// Do not serve files through symlinks.
+    #[arg(long)]
+    no_follow_symlinks: bool,
+    /// Seconds to wait for the requests in progress when stopping, if they
+    /// don't finish in time the exit status is 1.
+    #[arg(long, default_value_t = DEFAULT_GRACE_PERIOD)]
+    grace_period: u64,
+}
+
+impl CommandRun for ServeStaticCommand {
+    fn run(&self) {
+        init_log(self.level);
+
+        let dir = PathBuf::from(&self.dir)
+            .canonicalize()
+            .unwrap_or_else(|err| {
+                let err = Error::Undefined(format!("invalid static dir path: {}", err));
+                err.fatal();
+                exit(1);
+            });
+
+        let router = Router::new();
+        debug!("serving static dir: {}", dir.display());
+        let follow_symlinks = !self.no_follow_symlinks;
+        let handler = move |request: &mut Request| -> Response {
+            serve_static_dir(&dir, request, follow_symlinks)
+        };
+        let handler = middleware::compress(Box::new(handler));
+        let handler = middleware::decode_path(handler);
+        add_get_and_head(&router, "/", handler);
+        let server = start_server(&self.address, router, None);
+        run_servers(vec![server], Duration::from_secs(self.grace_period));
+    }
+}
+
+/// Serves the files of a directory generated by the pack command, the same
+/// way a static web server would.
+fn serve_static_dir(dir: &Path, request: &mut Request, follow_symlinks: bool) -> Response {
+    let uri = match request.uri.as_str() {
+        "" => "/",
+        uri => uri,
+    };
+    // Hidden files and parent segments are never served, don't even look for
+    // them.
+    if uri.split('/').any(|segment| segment.starts_with('.')) {
+        return not_found(dir);
+    }
+    let rel_path = uri.trim_start_matches('/');
+    let path = dir.join(rel_path);
+    let uri = if path.is_dir() {
+        // Directories are served from their index, the relative links in it
+        // only work if the url of the directory ends with a slash. The
+        // location has a single leading slash, as `//host` would be another
+        // site.
+        if !uri.ends_with('/') {
+            let location = utf8_percent_encode(rel_path, LOCATION_ENCODE_SET);
+            return redirect(&format!("/{}/", location));
+        }
+        format!("{}{}", uri, INDEX_FILE)
+    } else if !path.exists() && Path::new(rel_path).extension().is_none() {
+        // Extension-less urls are served from the html file with the same
+        // name, if there is one.
+        let html_uri = format!("{}.html", uri.trim_end_matches('/'));
+        match dir.join(html_uri.trim_start_matches('/')).is_file() {
+            true => html_uri,
+            false => uri.to_string(),
+        }
+    } else {
+        uri.to_string()
+    };
+    debug!("serving static dir, {} resolved to {}", request.uri, uri);
+    request.uri = uri;
+    let response = serve_static(
+        "/".into(),
+        dir.to_path_buf(),
+        request,
+        None,
+        follow_symlinks,
+    );
+    if matches!(response.status, StatusCode::NotFound) {
+        return not_found(dir);
+    }
+    response
+}
+
+/// Returns a not found response with the custom 404 page of the directory, if
+/// it has one.
+fn not_found(dir: &Path) -> Response {
+    let content = match fs::read(dir.join(NOT_FOUND_FILE)) {
+        Ok(content) => content,
+        Err(_) => return Response::from_status(StatusCode::NotFound),
+    };
+    let content_len = content.len() as u64;
+    let mut response = Response::from_content(Cursor::new(content), content_len, mime::TEXT_HTML);
+    response.status = StatusCode::NotFound;
+    response
+}
@@ -0,0 +1,273 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use clap::Args;
+use log::debug;
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection};
+use rustls_pemfile::Item;
+use wruster::{
+    http::{Request, Response, StatusCode},
+    router::{HttpHandler, Router},
+};
+
+use super::{add_get_and_head, redirect, request_header};
+use crate::errors::Error;
+
+const HTTPS_PORT: &str = "443";
+
+#[derive(Args, Debug)]
+pub(crate) struct TlsArgs {
+    /// Path to a PEM file with the certificate of the blog, it's served
+    /// through https when given. It can contain the whole chain, e.g.:
+    /// `fullchain.pem`, starting with the certificate of the blog.
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Path to a PEM file with the private key of the certificate.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Address of a plain http listener that redirects to https, for example:
+    /// 0.0.0.0:80
+    #[arg(long, requires = "tls_cert")]
+    http_redirect: Option<String>,
+}
+
+impl TlsArgs {
+    pub(crate) fn http_redirect(&self) -> Option<&str> {
+        self.http_redirect.as_deref()
+    }
+
+    /// Reads the certificate chain and the private key, if given.
+    pub(crate) fn load(&self) -> Result<Option<Tls>, Error> {
+        let (cert_path, key_path) = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+            _ => return Ok(None),
+        };
+        let certs: Vec<Certificate> = read_pem_items(cert_path)?
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::X509Certificate(cert) => Some(Certificate(cert)),
+                _ => None,
+            })
+            .collect();
+        if certs.is_empty() {
+            return Err(Error::Undefined(format!(
+                "no certificate found in {}",
+                cert_path.display()
+            )));
+        }
+        let key = read_pem_items(key_path)?
+            .into_iter()
+            .find_map(|item| match item {
+                Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                Error::Undefined(format!("no private key found in {}", key_path.display()))
+            })?;
+        // The whole chain is sent to the clients, they need the intermediate
+        // certificates to verify the certificate of the blog.
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|err| {
+                Error::Undefined(format!(
+                    "invalid certificate {} or private key {}: {}",
+                    cert_path.display(),
+                    key_path.display(),
+                    err
+                ))
+            })?;
+        Ok(Some(Tls {
+            config: Arc::new(config),
+        }))
+    }
+}
+
+/// TLS configuration, with the certificate chain and the private key, used
+/// to serve the blog through https.
+pub(crate) struct Tls {
+    config: Arc<ServerConfig>,
+}
+
+/// Returns a free local address for the plain http server behind the https
+/// proxy.
+pub(crate) fn local_address() -> io::Result<SocketAddr> {
+    TcpListener::bind("127.0.0.1:0")?.local_addr()
+}
+
+/// Https listener that decrypts the connections and forwards them to a plain
+/// http server, as wruster only configures TLS with a single certificate.
+pub(crate) struct Proxy {
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+}
+
+impl Proxy {
+    /// Listens in the given address, the connections wait until the proxy
+    /// runs.
+    pub(crate) fn bind(address: &str, tls: Tls) -> io::Result<Proxy> {
+        Ok(Proxy {
+            listener: TcpListener::bind(address)?,
+            config: tls.config,
+        })
+    }
+
+    /// Accepts the connections in a new thread, and forwards each one to the
+    /// server in the `backend` address in its own thread.
+    pub(crate) fn run(self, backend: SocketAddr) {
+        thread::spawn(move || {
+            for client in self.listener.incoming() {
+                let client = match client {
+                    Ok(client) => client,
+                    Err(err) => {
+                        debug!("https proxy, error accepting a connection: {}", err);
+                        continue;
+                    }
+                };
+                let config = self.config.clone();
+                thread::spawn(move || {
+                    if let Err(err) = forward(client, backend, config) {
+                        debug!("https proxy, error forwarding a connection: {}", err);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Forwards the requests of the client to the backend, and its responses back
+/// to the client. The responses are forwarded in their own thread, the
+/// client can send more data while they are written.
+fn forward(client: TcpStream, backend: SocketAddr, config: Arc<ServerConfig>) -> io::Result<()> {
+    let server = TcpStream::connect(backend)?;
+    let connection = ServerConnection::new(config).map_err(io::Error::other)?;
+    let connection = Arc::new(Mutex::new(connection));
+    let responses = {
+        let connection = connection.clone();
+        let client = client.try_clone()?;
+        let server = server.try_clone()?;
+        thread::spawn(move || forward_responses(server, &connection, client))
+    };
+    let result = forward_requests(client, &connection, &server);
+    // Tells the backend there are no more requests, so it closes its side.
+    let _ = server.shutdown(Shutdown::Write);
+    let responses_result = responses
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("forwarding panicked")));
+    result.and(responses_result)
+}
+
+/// Decrypts the data of the client and writes it to the backend until the
+/// client closes the connection.
+fn forward_requests(
+    mut client: TcpStream,
+    connection: &Mutex<ServerConnection>,
+    mut server: &TcpStream,
+) -> io::Result<()> {
+    let mut buf = [0; 16 * 1024];
+    let mut plaintext = Vec::new();
+    loop {
+        // The socket is read without holding the connection, so the responses
+        // can be written meanwhile.
+        let len = client.read(&mut buf)?;
+        if len == 0 {
+            return Ok(());
+        }
+        let mut data = &buf[..len];
+        let mut peer_has_closed = false;
+        plaintext.clear();
+        {
+            let mut connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+            while !data.is_empty() {
+                connection.read_tls(&mut data)?;
+                let state = match connection.process_new_packets() {
+                    Ok(state) => state,
+                    Err(err) => {
+                        // Send the alert to the client.
+                        let _ = connection.write_tls(&mut client);
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                    }
+                };
+                let start = plaintext.len();
+                plaintext.resize(start + state.plaintext_bytes_to_read(), 0);
+                connection.reader().read_exact(&mut plaintext[start..])?;
+                peer_has_closed = state.peer_has_closed();
+            }
+            // The handshake messages.
+            while connection.wants_write() {
+                connection.write_tls(&mut client)?;
+            }
+        }
+        server.write_all(&plaintext)?;
+        if peer_has_closed {
+            return Ok(());
+        }
+    }
+}
+
+/// Encrypts the data of the backend and writes it to the client until the
+/// backend closes the connection, then it closes the connection with the
+/// client.
+fn forward_responses(
+    mut server: TcpStream,
+    connection: &Mutex<ServerConnection>,
+    mut client: TcpStream,
+) -> io::Result<()> {
+    let mut buf = [0; 16 * 1024];
+    loop {
+        let len = server.read(&mut buf)?;
+        let mut connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+        match len {
+            0 => connection.send_close_notify(),
+            len => connection.writer().write_all(&buf[..len])?,
+        }
+        while connection.wants_write() {
+            connection.write_tls(&mut client)?;
+        }
+        if len == 0 {
+            // This also stops reading the requests of the client.
+            return client.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn read_pem_items(path: &Path) -> Result<Vec<Item>, Error> {
+    let file = fs::File::open(path)
+        .map_err(|err| Error::Undefined(format!("error opening {}: {}", path.display(), err)))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))?;
+    Ok(items)
+}
+
+/// Returns a router that redirects all the requests to the same url using
+/// https, in the port of the given address.
+pub(crate) fn build_redirect_router(https_address: &str) -> Router {
+    let port = match https_address.rsplit_once(':') {
+        Some((_, port)) if port != HTTPS_PORT => format!(":{}", port),
+        _ => String::new(),
+    };
+    let router = Router::new();
+    let handler = move |request: &mut Request| -> Response {
+        // The port of the host is the one of the plain http listener.
+        let host = match request_header(request, "Host") {
+            Some(host) => match host.rsplit_once(':') {
+                // The colons of an ipv6 address are inside brackets.
+                Some((host, port)) if !port.contains(']') => host,
+                _ => host,
+            },
+            None => return Response::from_status(StatusCode::BadRequest),
+        };
+        let location = format!("https://{}{}{}", host, port, request.uri);
+        debug!("redirecting {} to {}", request.uri, location);
+        redirect(&location)
+    };
+    let handler: HttpHandler = Box::new(handler);
+    add_get_and_head(&router, "/", handler);
+    router
+}
@@ -1,10 +1,15 @@
+use std::collections::BTreeMap;
+
 use chrono::{self, Utc};
-use ramhorns::{self, Content};
+use ramhorns::{self, encoding::Encoder, traits::ContentSequence, Content, Section};
 
-use crate::errors::Error;
+use crate::{content, errors::Error};
 
 const MAIN_TEMPLATE: &str = "index.html";
 const POST_TEMPLATE: &str = "post.html";
+const PAGE_TEMPLATE: &str = "page.html";
+const NOT_FOUND_TEMPLATE: &str = "404.html";
+const SERVER_ERROR_TEMPLATE: &str = "500.html";
 
 pub struct Main {
     templates: ramhorns::Ramhorns,
@@ -46,16 +51,74 @@ impl Post {
     }
 }
 
+pub struct Page {
+    templates: ramhorns::Ramhorns,
+}
+
+impl Page {
+    /// Reads the page template. As the template is optional it returns None
+    /// if the template does not exist.
+    pub(crate) fn read_from_dir(templates_dir: &str) -> Result<Option<Page>, Error> {
+        let templates = ramhorns::Ramhorns::from_folder(templates_dir).map_err(Error::from)?;
+        if templates.get(PAGE_TEMPLATE).is_none() {
+            return Ok(None);
+        }
+        let page = Page { templates };
+        Ok(Some(page))
+    }
+
+    pub(crate) fn render(&self, model: &PageTemplateModel) -> String {
+        let tpl = self.templates.get(PAGE_TEMPLATE).unwrap();
+        tpl.render(model)
+    }
+}
+
+/// Templates of the pages shown when a request fails, all of them are
+/// optional.
+pub struct ErrorPages {
+    templates: ramhorns::Ramhorns,
+}
+
+impl ErrorPages {
+    pub(crate) fn read_from_dir(templates_dir: &str) -> Result<ErrorPages, Error> {
+        let templates = ramhorns::Ramhorns::from_folder(templates_dir).map_err(Error::from)?;
+        Ok(ErrorPages { templates })
+    }
+
+    /// Renders the not found page, returns None if there is no template for
+    /// it.
+    pub(crate) fn render_not_found(&self, model: &ErrorTemplateModel) -> Option<String> {
+        let tpl = self.templates.get(NOT_FOUND_TEMPLATE)?;
+        Some(tpl.render(model))
+    }
+
+    /// Renders the internal server error page, returns None if there is no
+    /// template for it.
+    pub(crate) fn render_server_error(&self, model: &ErrorTemplateModel) -> Option<String> {
+        let tpl = self.templates.get(SERVER_ERROR_TEMPLATE)?;
+        Some(tpl.render(model))
+    }
+}
+
 pub struct Blog {
     main: Main,
     post: Post,
+    page: Option<Page>,
+    errors: ErrorPages,
 }
 
 impl Blog {
     pub(crate) fn read_from_dir(templates_dir: &str) -> Result<Blog, Error> {
         let main = Main::read_from_dir(templates_dir)?;
         let post = Post::read_from_dir(templates_dir)?;
-        let blog = Blog { main, post };
+        let page = Page::read_from_dir(templates_dir)?;
+        let errors = ErrorPages::read_from_dir(templates_dir)?;
+        let blog = Blog {
+            main,
+            post,
+            page,
+            errors,
+        };
         Ok(blog)
     }
 
@@ -67,8 +130,58 @@ impl Blog {
         self.post.render(model)
     }
 
-    pub(crate) fn parts(self) -> (Main, Post) {
-        (self.main, self.post)
+    pub(crate) fn has_page_template(&self) -> bool {
+        self.page.is_some()
+    }
+
+    pub(crate) fn render_page(&self, model: &PageTemplateModel) -> Result<String, Error> {
+        match &self.page {
+            Some(page) => Ok(page.render(model)),
+            None => Err(Error::NoPageTemplateFound),
+        }
+    }
+
+    pub(crate) fn render_not_found(&self, model: &ErrorTemplateModel) -> Option<String> {
+        self.errors.render_not_found(model)
+    }
+
+    pub(crate) fn parts(self) -> (Main, Post, Option<Page>, ErrorPages) {
+        (self.main, self.post, self.page, self.errors)
+    }
+}
+
+/// Data about the whole site, available to every template under `site`.
+#[derive(Content, Debug, Clone)]
+pub struct SiteTemplateModel {
+    pub title: String,
+    pub twitter: String,
+    pub home_content: String,
+    pub author: String,
+    pub year: u16,
+    pub build_time: String,
+    pub version: String,
+    /// Public url of the blog without the trailing slash.
+    pub base_url: String,
+    /// Path of the public url of the blog without the trailing slash, it
+    /// must prefix the links to the assets of the templates.
+    pub base_path: String,
+    pub extra: Extra,
+}
+
+impl From<&content::Blog> for SiteTemplateModel {
+    fn from(blog: &content::Blog) -> Self {
+        SiteTemplateModel {
+            title: blog.title.clone(),
+            twitter: blog.twitter.clone(),
+            home_content: blog.home_content.clone(),
+            author: blog.author.clone(),
+            year: blog.year,
+            build_time: Utc::now().format("%Y-%m-%d %H:%M").to_string(),
+            version: env!("CARGO_PKG_VERSION").into(),
+            base_url: blog.base_url().url().into(),
+            base_path: blog.base_url().path().into(),
+            extra: Extra::from(&blog.extra),
+        }
     }
 }
 
@@ -80,6 +193,10 @@ pub struct MainTemplateModel {
     pub author: String,
     pub year: u16,
     pub posts: Vec<PostTemplateModel>,
+    /// Full url of the page, including the base url of the blog.
+    pub canonical_url: String,
+    pub site: SiteTemplateModel,
+    pub extra: Extra,
 }
 
 #[derive(Content, Debug)]
@@ -95,6 +212,30 @@ pub struct PostTemplateModel {
     pub file_name: String,
     pub author: String,
     pub year: String,
+    /// Full url of the page, including the base url of the blog.
+    pub canonical_url: String,
+    pub site: SiteTemplateModel,
+    pub extra: Extra,
+}
+
+#[derive(Content, Debug)]
+pub struct ErrorTemplateModel {
+    /// Status code of the response, e.g.: 404.
+    pub status: u16,
+    pub root_page: String,
+    pub site: SiteTemplateModel,
+}
+
+#[derive(Content, Debug)]
+pub struct PageTemplateModel {
+    pub title: String,
+    pub root_page: String,
+    pub content: String,
+    pub slug: String,
+    /// Full url of the page, including the base url of the blog.
+    pub canonical_url: String,
+    pub site: SiteTemplateModel,
+    pub extra: Extra,
 }
 
 fn render_date_time<E>(s: &DateTime, enc: &mut E) -> Result<(), E::Error>
@@ -112,3 +253,152 @@ impl Content for DateTime {}
 
 #[derive(Content, Debug)]
 pub struct Tag(pub String);
+
+/// Custom front matter values, exposed to the templates as they are defined
+/// in the front matter, e.g.: `{{extra.cover_image}}`.
+#[derive(Debug, Clone)]
+pub enum Extra {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    List(Vec<Extra>),
+    Map(BTreeMap<String, Extra>),
+}
+
+impl From<&serde_json::Value> for Extra {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Extra::Null,
+            serde_json::Value::Bool(value) => Extra::Bool(*value),
+            serde_json::Value::Number(value) => Extra::Number(value.to_string()),
+            serde_json::Value::String(value) => Extra::String(value.clone()),
+            serde_json::Value::Array(values) => {
+                Extra::List(values.iter().map(Extra::from).collect())
+            }
+            serde_json::Value::Object(values) => Extra::Map(
+                values
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Extra::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&BTreeMap<String, serde_json::Value>> for Extra {
+    fn from(values: &BTreeMap<String, serde_json::Value>) -> Self {
+        Extra::Map(
+            values
+                .iter()
+                .map(|(key, value)| (key.clone(), Extra::from(value)))
+                .collect(),
+        )
+    }
+}
+
+// The Content implementation just delegates to the implementations ramhorns
+// already provides for the wrapped types.
+impl Content for Extra {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Extra::Null => false,
+            Extra::Bool(value) => value.is_truthy(),
+            Extra::Number(value) | Extra::String(value) => value.is_truthy(),
+            Extra::List(values) => values.is_truthy(),
+            Extra::Map(values) => values.is_truthy(),
+        }
+    }
+
+    fn render_escaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        match self {
+            Extra::Null => Ok(()),
+            Extra::Bool(value) => value.render_escaped(encoder),
+            Extra::Number(value) | Extra::String(value) => value.render_escaped(encoder),
+            Extra::List(values) => values.render_escaped(encoder),
+            Extra::Map(values) => values.render_escaped(encoder),
+        }
+    }
+
+    fn render_unescaped<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        match self {
+            Extra::Null => Ok(()),
+            Extra::Bool(value) => value.render_unescaped(encoder),
+            Extra::Number(value) | Extra::String(value) => value.render_unescaped(encoder),
+            Extra::List(values) => values.render_unescaped(encoder),
+            Extra::Map(values) => values.render_unescaped(encoder),
+        }
+    }
+
+    fn render_section<C, E>(&self, section: Section<C>, encoder: &mut E) -> Result<(), E::Error>
+    where
+        C: ContentSequence,
+        E: Encoder,
+    {
+        match self {
+            Extra::Null => Ok(()),
+            Extra::Bool(value) => value.render_section(section, encoder),
+            Extra::Number(value) | Extra::String(value) => value.render_section(section, encoder),
+            Extra::List(values) => values.render_section(section, encoder),
+            Extra::Map(values) => values.render_section(section, encoder),
+        }
+    }
+
+    fn render_field_escaped<E: Encoder>(
+        &self,
+        hash: u64,
+        name: &str,
+        encoder: &mut E,
+    ) -> Result<bool, E::Error> {
+        match self {
+            Extra::Map(values) => values.render_field_escaped(hash, name, encoder),
+            _ => Ok(false),
+        }
+    }
+
+    fn render_field_unescaped<E: Encoder>(
+        &self,
+        hash: u64,
+        name: &str,
+        encoder: &mut E,
+    ) -> Result<bool, E::Error> {
+        match self {
+            Extra::Map(values) => values.render_field_unescaped(hash, name, encoder),
+            _ => Ok(false),
+        }
+    }
+
+    fn render_field_section<C, E>(
+        &self,
+        hash: u64,
+        name: &str,
+        section: Section<C>,
+        encoder: &mut E,
+    ) -> Result<bool, E::Error>
+    where
+        C: ContentSequence,
+        E: Encoder,
+    {
+        match self {
+            Extra::Map(values) => values.render_field_section(hash, name, section, encoder),
+            _ => Ok(false),
+        }
+    }
+
+    fn render_field_inverse<C, E>(
+        &self,
+        hash: u64,
+        name: &str,
+        section: Section<C>,
+        encoder: &mut E,
+    ) -> Result<bool, E::Error>
+    where
+        C: ContentSequence,
+        E: Encoder,
+    {
+        match self {
+            Extra::Map(values) => values.render_field_inverse(hash, name, section, encoder),
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+use crate::content::DateTime;
+
+/// Pattern of the url of the posts when the blog doesn't define one.
+pub(crate) const DEFAULT_PERMALINK: &str = "/posts/:slug";
+/// Url of the main page of the blog.
+pub(crate) const ROOT_URL: &str = "/";
+/// Directory, relative to the root of the site, with the assets of the
+/// templates.
+pub(crate) const ASSETS_DIR: &str = "assets";
+/// Directory, relative to the root of the site, with the assets of the posts.
+pub(crate) const POST_ASSETS_DIR: &str = "post_assets";
+/// File served for the urls that end with a slash.
+pub(crate) const INDEX_FILE: &str = "index.html";
+
+/// Public url of the site, e.g.: `https://example.com/blog/`. The urls of the
+/// pages are relative to the root of the site, and must be prefixed with the
+/// base url to link to them, so the site can be hosted under a subpath.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BaseUrl {
+    /// The base url without the trailing slash, empty if not defined.
+    url: String,
+    /// The path of the base url without the trailing slash, e.g.: `/blog`.
+    path: String,
+}
+
+impl BaseUrl {
+    pub(crate) fn new(base_url: &str) -> BaseUrl {
+        let url = base_url.trim_end_matches('/').to_string();
+        let path = match url.find("://") {
+            Some(scheme_end) => {
+                let host_start = scheme_end + "://".len();
+                match url[host_start..].find('/') {
+                    Some(path_start) => url[host_start + path_start..].to_string(),
+                    None => String::new(),
+                }
+            }
+            // A base url without scheme and host is just a path.
+            None => url.clone(),
+        };
+        BaseUrl { url, path }
+    }
+
+    /// Returns the base url, without the trailing slash.
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the path of the base url, without the trailing slash.
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the link to the given url of the site, relative to the host.
+    pub(crate) fn link(&self, url: &str) -> String {
+        format!("{}{}", self.path, url)
+    }
+
+    /// Returns the full url of the given url of the site, as used in
+    /// canonical links, it's relative to the host if the base url is not
+    /// defined.
+    pub(crate) fn absolute(&self, url: &str) -> String {
+        format!("{}{}", self.url, url)
+    }
+}
+
+/// Builds the url of a post by replacing the placeholders in the permalink
+/// pattern: `:year`, `:month`, `:day` and `:slug`.
+pub(crate) fn post_url(permalink: &str, slug: &str, date: &DateTime) -> String {
+    permalink
+        .replace(":year", &date.0.format("%Y").to_string())
+        .replace(":month", &date.0.format("%m").to_string())
+        .replace(":day", &date.0.format("%d").to_string())
+        .replace(":slug", slug)
+}
+
+/// Returns the url of the standalone page with the given slug.
+pub(crate) fn page_url(slug: &str) -> String {
+    format!("/{}", slug)
+}
+
+/// Returns the url of the directory with the assets of a post, given the
+/// directory of the assets relative to the posts directory.
+pub(crate) fn post_assets_url(assets_dir: &Path) -> String {
+    let mut url = format!("/{}", POST_ASSETS_DIR);
+    for component in assets_dir.components() {
+        url.push('/');
+        url.push_str(&component.as_os_str().to_string_lossy());
+    }
+    url
+}
+
+/// Returns true if the slug can be used as the name of a published file: it's
+/// not empty, it's not hidden, e.g.: `.htaccess`, and it has no separators or
+/// NUL characters.
+pub(crate) fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty() && !slug.starts_with('.') && !slug.contains(['/', '\\', '\0'])
+}
+
+/// Returns true if the url can be published as a file inside the root of the
+/// site: it's absolute and all its segments are valid slugs, only the last
+/// one can be empty, for the urls of directories, except for the root url.
+/// This rejects `.` and `..` segments, so the file of the url can't be
+/// outside the root.
+pub(crate) fn is_valid_url(url: &str) -> bool {
+    let path = match url.strip_prefix('/') {
+        Some(path) => path.strip_suffix('/').unwrap_or(path),
+        None => return false,
+    };
+    path.split('/').all(is_valid_slug)
+}
+
+/// Ensures the given url starts with a slash.
+pub(crate) fn absolute_url(url: &str) -> String {
+    match url.starts_with('/') {
+        true => url.into(),
+        false => format!("/{}", url),
+    }
+}
+
+/// Returns the path, relative to the root of a packed site, of the file
+/// served for the given url. Urls ending with a slash are the index of a
+/// directory, and the rest are html files, as most static hosts serve
+/// `/about` from `about.html`. The permalinks end either with a slash, with
+/// the slug or with `.html`, so a dot in a slug, e.g.: `release-1.2`, is
+/// part of the name of the file, not its extension.
+pub(crate) fn url_file_path(url: &str) -> PathBuf {
+    let mut path = PathBuf::from(url.trim_start_matches('/'));
+    if url.ends_with('/') || path.as_os_str().is_empty() {
+        path.push(INDEX_FILE);
+    } else if !url.ends_with(".html") {
+        let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".html");
+        path.set_file_name(file_name);
+    }
+    path
+}
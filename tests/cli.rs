@@ -1,6 +1,7 @@
 use assert_cmd::prelude::*;
-use std::io::{BufRead, BufReader, Read};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::{fs, thread};
 
@@ -45,6 +46,1228 @@ fn serves_home_page() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn serves_pages() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let page_url = format!("{}/about", blog_url);
+    let page_result = ureq::get(&page_url).call()?;
+    assert_eq!(
+        read_test_file("expected-page.html"),
+        page_result.into_string().unwrap()
+    );
+
+    let missing_page_url = format!("{}/missing", blog_url);
+    let missing_page_result = ureq::get(&missing_page_url).call();
+    assert!(matches!(
+        missing_page_result,
+        Err(ureq::Error::Status(404, _))
+    ));
+
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn packs_and_serves_pages_under_their_slugs() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let slug_content = test_output_dir("page-slug-content");
+    copy_dir(PathBuf::from(content).as_path(), &slug_content)?;
+    fs::write(
+        slug_content.join("pages/contact-me.md"),
+        "---\ntitle: Contact\nslug: contact\n---\nWrite me.\n",
+    )?;
+    let output = test_output_dir("page-slug-output");
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&slug_content)
+        .arg(&output);
+    cmd.assert().success();
+    assert!(output.join("contact.html").exists());
+    assert!(!output.join("contact-me.html").exists());
+
+    let (mut process, blog_url) = serve_blog(&slug_content, &[])?;
+    let page_content = ureq::get(&format!("{}/contact", blog_url))
+        .call()?
+        .into_string()?;
+    assert!(page_content.contains("Write me."));
+    let file_name_result = ureq::get(&format!("{}/contact-me", blog_url)).call();
+    assert!(matches!(file_name_result, Err(ureq::Error::Status(404, _))));
+    process.kill().unwrap();
+
+    // A page can't replace the files generated for the blog.
+    fs::write(
+        slug_content.join("pages/home.md"),
+        "---\ntitle: Home\nslug: index\n---\nHome.\n",
+    )?;
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&slug_content)
+        .arg(&output);
+    cmd.assert().failure();
+
+    fs::remove_dir_all(slug_content)?;
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn packs_and_serves_posts_under_their_permalinks() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let permalink_content = test_output_dir("permalink-content");
+    copy_dir(PathBuf::from(content).as_path(), &permalink_content)?;
+    let blog_file = permalink_content.join("blog.md");
+    let blog = fs::read_to_string(&blog_file)?;
+    fs::write(
+        &blog_file,
+        blog.replacen("---\n", "---\npermalink: /:year/:month/:slug/\n", 1),
+    )?;
+    let post_file = permalink_content.join("posts/post-1.md");
+    let post = fs::read_to_string(&post_file)?;
+    fs::write(
+        &post_file,
+        post.replacen("---\n", "---\nslug: first-post\n", 1),
+    )?;
+    let output = test_output_dir("permalink-output");
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&permalink_content)
+        .arg(&output);
+    cmd.assert().success();
+    assert!(output.join("2023/04/first-post/index.html").exists());
+    assert!(output.join("2023/03/bundle-post/index.html").exists());
+    assert!(!output.join("posts/post-1.html").exists());
+    let home = fs::read_to_string(output.join("index.html"))?;
+    assert!(home.contains("/2023/04/first-post/"));
+
+    let (mut process, blog_url) = serve_blog(&permalink_content, &[])?;
+    let post_content = ureq::get(&format!("{}/2023/04/first-post/", blog_url))
+        .call()?
+        .into_string()?;
+    assert!(post_content.contains("A post"));
+    let file_name_result = ureq::get(&format!("{}/posts/post-1", blog_url)).call();
+    assert!(matches!(file_name_result, Err(ureq::Error::Status(404, _))));
+    process.kill().unwrap();
+
+    fs::remove_dir_all(permalink_content)?;
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn packs_and_serves_slugs_with_dots() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let dotted_content = test_output_dir("dotted-slug-content");
+    copy_dir(PathBuf::from(content).as_path(), &dotted_content)?;
+    fs::write(
+        dotted_content.join("posts/release.md"),
+        "---\ntitle: Release\nauthor: me\ndate: \"2023-05-01 10:00\"\n\
+        summary: a release\ntags: []\nslug: release-1.2\n---\nReleased.\n",
+    )?;
+    let output = test_output_dir("dotted-slug-output");
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&dotted_content)
+        .arg(&output);
+    cmd.assert().success();
+    assert!(output.join("posts/release-1.2.html").exists());
+
+    let (mut process, blog_url) = serve_blog(&dotted_content, &[])?;
+    let post_content = ureq::get(&format!("{}/posts/release-1.2", blog_url))
+        .call()?
+        .into_string()?;
+    assert!(post_content.contains("Released."));
+    process.kill().unwrap();
+
+    fs::remove_dir_all(dotted_content)?;
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn serves_bundle_posts_and_assets() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let post_url = format!("{}/posts/bundle-post", blog_url);
+    let post_content = ureq::get(&post_url).call()?.into_string()?;
+    assert!(post_content.contains("src=\"/post_assets/2023/bundle-post/diagram.svg\""));
+
+    let asset_url = format!("{}/post_assets/2023/bundle-post/diagram.svg", blog_url);
+    let asset_result = ureq::get(&asset_url).call()?;
+    assert_eq!(200, asset_result.status());
+
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn answers_conditional_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    for path in [
+        "/",
+        "/posts/post-1",
+        "/post_assets/2023/bundle-post/diagram.svg",
+    ] {
+        let url = format!("{}{}", blog_url, path);
+        let response = ureq::get(&url).call()?;
+        let etag = response.header("ETag").expect("etag header").to_string();
+
+        let response = ureq::get(&url).set("If-None-Match", &etag).call()?;
+        assert_eq!(304, response.status(), "{}", path);
+
+        let response = ureq::get(&url).set("If-None-Match", "\"other\"").call()?;
+        assert_eq!(200, response.status(), "{}", path);
+    }
+
+    // The rendered pages depend on many files, only the static files have a
+    // last modified date.
+    let response = ureq::get(&format!("{}/posts/post-1", blog_url)).call()?;
+    assert_eq!(None, response.header("Last-Modified"));
+    let url = format!("{}/post_assets/2023/bundle-post/diagram.svg", blog_url);
+    let response = ureq::get(&url).call()?;
+    let last_modified = response
+        .header("Last-Modified")
+        .expect("last modified header")
+        .to_string();
+    let response = ureq::get(&url)
+        .set("If-Modified-Since", &last_modified)
+        .call()?;
+    assert_eq!(304, response.status());
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn sets_the_cache_policies_of_the_routes() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) =
+        serve_test_blog_with_args(&["--cache", "posts=public, max-age=60"])?;
+
+    let cache_control = |path: &str| -> Result<Option<String>, ureq::Error> {
+        let response = ureq::get(&format!("{}{}", blog_url, path)).call()?;
+        Ok(response.header("Cache-Control").map(String::from))
+    };
+    assert_eq!(Some("public, max-age=300".into()), cache_control("/")?);
+    assert_eq!(
+        Some("public, max-age=60".into()),
+        cache_control("/posts/post-1")?
+    );
+    assert_eq!(
+        Some("public, max-age=86400".into()),
+        cache_control("/assets/main.css")?
+    );
+    process.kill().unwrap();
+
+    let (mut process, blog_url) = serve_test_blog_with_args(&["--no-cache"])?;
+    let response = ureq::get(&format!("{}/about", blog_url)).call()?;
+    assert_eq!(Some("no-cache"), response.header("Cache-Control"));
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn compresses_the_responses() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let content_encoding = |path: &str, accept_encoding: &str| {
+        let (status, headers) = raw_get(&blog_url, path, &[("Accept-Encoding", accept_encoding)])?;
+        assert_eq!(200, status, "{}", path);
+        assert!(headers.contains(&("vary".into(), "Accept-Encoding".into())));
+        let encoding = headers
+            .into_iter()
+            .find(|(name, _)| name == "content-encoding")
+            .map(|(_, value)| value);
+        Ok::<_, std::io::Error>(encoding)
+    };
+    assert_eq!(
+        Some("br".into()),
+        content_encoding("/assets/main.css", "gzip, br")?
+    );
+    assert_eq!(
+        Some("gzip".into()),
+        content_encoding("/assets/main.css", "br;q=0, *")?
+    );
+    assert_eq!(None, content_encoding("/assets/main.css", "identity")?);
+    // Small responses are not compressed.
+    assert_eq!(None, content_encoding("/assets/dark.css", "gzip")?);
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn serves_precompressed_files() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("serve-precompressed");
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack").arg(&templates).arg(&content).arg(&output);
+    cmd.assert().success();
+    let precompressed = b"precompressed";
+    fs::write(output.join("assets/main.css.gz"), precompressed)?;
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let port = get_free_port();
+    cmd.arg("serve-static")
+        .arg(&output)
+        .arg(format!("0.0.0.0:{}", port))
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "listening on");
+    let base_url = format!("http://localhost:{}", port);
+
+    let (status, headers) = raw_get(
+        &base_url,
+        "/assets/main.css",
+        &[("Accept-Encoding", "gzip")],
+    )?;
+    assert_eq!(200, status);
+    assert!(headers.contains(&("content-encoding".into(), "gzip".into())));
+    assert!(headers.contains(&("content-length".into(), precompressed.len().to_string())));
+    assert!(headers.contains(&("content-type".into(), "text/css".into())));
+    process.kill().unwrap();
+
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn serves_ranges_of_static_files() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+    let css = fs::read(Path::new(&test_blog_dir().1).join("assets/main.css"))?;
+    let url = format!("{}/assets/main.css", blog_url);
+
+    let response = ureq::get(&url).set("Range", "bytes=0-99").call()?;
+    assert_eq!(206, response.status());
+    assert_eq!(Some("bytes"), response.header("Accept-Ranges"));
+    let content_range = format!("bytes 0-99/{}", css.len());
+    assert_eq!(
+        Some(content_range.as_str()),
+        response.header("Content-Range")
+    );
+    let mut content = Vec::new();
+    response.into_reader().read_to_end(&mut content)?;
+    assert_eq!(&css[..100], content.as_slice());
+
+    let response = ureq::get(&url).set("Range", "bytes=0-9, -10").call()?;
+    assert_eq!(206, response.status());
+    assert!(response.content_type().starts_with("multipart/byteranges"));
+    let content = response.into_string()?;
+    let last_range = format!(
+        "Content-Range: bytes {}-{}/{}",
+        css.len() - 10,
+        css.len() - 1,
+        css.len()
+    );
+    assert!(content.contains(&last_range));
+
+    match ureq::get(&url).set("Range", "bytes=999999-").call() {
+        Err(ureq::Error::Status(code, _)) => assert_eq!(416, code),
+        _ => panic!("expected a range not satisfiable response"),
+    }
+
+    // The ranges are ignored if the file changed.
+    let response = ureq::get(&url)
+        .set("Range", "bytes=0-99")
+        .set("If-Range", "\"other\"")
+        .call()?;
+    assert_eq!(200, response.status());
+
+    // The entity tag in If-Range must be the one of the same representation
+    // of the file, not the one of a compressed version of it.
+    let (_, headers) = raw_get(&blog_url, "/assets/main.css", &[])?;
+    let etag = headers
+        .iter()
+        .find(|(name, _)| name == "etag")
+        .map(|(_, value)| value.clone())
+        .expect("etag header");
+    let (status, _) = raw_get(
+        &blog_url,
+        "/assets/main.css",
+        &[("Range", "bytes=0-99"), ("If-Range", &etag)],
+    )?;
+    assert_eq!(206, status);
+    let encoded_etag = format!("{}-br\"", etag.trim_end_matches('"'));
+    let (status, _) = raw_get(
+        &blog_url,
+        "/assets/main.css",
+        &[("Range", "bytes=0-99"), ("If-Range", &encoded_etag)],
+    )?;
+    assert_eq!(200, status);
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn answers_head_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let header = |headers: &[(String, String)], name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+    for path in [
+        "/",
+        "/posts/post-1",
+        "/about",
+        "/assets/main.css",
+        "/missing",
+    ] {
+        let (get_status, get_headers) = raw_request("GET", &blog_url, path, &[])?;
+        let (head_status, head_headers) = raw_request("HEAD", &blog_url, path, &[])?;
+        assert_eq!(get_status, head_status, "{}", path);
+        for name in ["content-length", "content-type", "etag"] {
+            assert_eq!(
+                header(&get_headers, name),
+                header(&head_headers, name),
+                "{} {}",
+                path,
+                name
+            );
+        }
+    }
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn adds_the_security_headers() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+    let response = ureq::get(&format!("{}/", blog_url)).call()?;
+    assert_eq!(Some("nosniff"), response.header("X-Content-Type-Options"));
+    assert!(response.header("Content-Security-Policy").is_some());
+    assert!(response.header("Strict-Transport-Security").is_none());
+    match ureq::get(&format!("{}/missing", blog_url)).call() {
+        Err(ureq::Error::Status(404, response)) => {
+            assert_eq!(Some("nosniff"), response.header("X-Content-Type-Options"))
+        }
+        _ => panic!("expected a not found response"),
+    }
+    process.kill().unwrap();
+
+    let (mut process, blog_url) = serve_test_blog_with_args(&[
+        "--hsts",
+        "--header",
+        "Referrer-Policy=no-referrer",
+        "--header",
+        "Permissions-Policy=",
+    ])?;
+    let response = ureq::get(&format!("{}/about", blog_url)).call()?;
+    assert!(response.header("Strict-Transport-Security").is_some());
+    assert_eq!(Some("no-referrer"), response.header("Referrer-Policy"));
+    assert!(response.header("Permissions-Policy").is_none());
+    process.kill().unwrap();
+
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("headers-file");
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&content)
+        .arg(&output)
+        .arg("--headers-file");
+    cmd.assert().success();
+    let headers = fs::read_to_string(output.join("_headers"))?;
+    assert!(headers.starts_with("/*\n"));
+    assert!(headers.contains("  X-Content-Type-Options: nosniff\n"));
+    fs::remove_dir_all(output)?;
+
+    Ok(())
+}
+
+#[test]
+fn serves_through_https() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let tls_dir = test_output_dir("tls");
+    fs::create_dir_all(&tls_dir)?;
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    fs::write(tls_dir.join("cert.pem"), cert.serialize_pem()?)?;
+    fs::write(tls_dir.join("key.pem"), cert.serialize_private_key_pem())?;
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let port = get_free_port();
+    let redirect_port = get_free_port();
+    cmd.arg("serve")
+        .arg(&templates)
+        .arg(&content)
+        .arg(format!("0.0.0.0:{}", port))
+        .arg("--tls-cert")
+        .arg(tls_dir.join("cert.pem"))
+        .arg("--tls-key")
+        .arg(tls_dir.join("key.pem"))
+        .arg("--http-redirect")
+        .arg(format!("0.0.0.0:{}", redirect_port))
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    // One line for the redirect listener and another for the blog.
+    wait_for_lines(&mut process, "listening on", 2);
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(&rustls::Certificate(cert.serialize_der()?))?;
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = rustls::ServerName::try_from("localhost")?;
+    let connection = rustls::ClientConnection::new(std::sync::Arc::new(config), server_name)?;
+    let stream = TcpStream::connect(format!("localhost:{}", port))?;
+    let mut stream = rustls::StreamOwned::new(connection, stream);
+    write!(
+        stream,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    assert!(status_line.starts_with("HTTP/1.1 200"), "{}", status_line);
+
+    let redirect_url = format!("http://localhost:{}", redirect_port);
+    let (status, headers) = raw_get(&redirect_url, "/posts/post-1", &[])?;
+    assert_eq!(301, status);
+    let location = format!("https://localhost:{}/posts/post-1", port);
+    assert!(headers.contains(&("location".into(), location)));
+    process.kill().unwrap();
+
+    fs::remove_dir_all(tls_dir)?;
+    Ok(())
+}
+
+#[test]
+fn serves_certificate_chains() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let tls_dir = test_output_dir("tls-chain");
+    fs::create_dir_all(&tls_dir)?;
+    let ca_params = |name: &str| {
+        let mut params = rcgen::CertificateParams::new(vec![]);
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, name);
+        params
+    };
+    let root = rcgen::Certificate::from_params(ca_params("root"))?;
+    let intermediate = rcgen::Certificate::from_params(ca_params("intermediate"))?;
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()])?;
+    // The client only trusts the root, so it needs the intermediate
+    // certificate to verify the one of the blog.
+    let chain = format!(
+        "{}{}",
+        cert.serialize_pem_with_signer(&intermediate)?,
+        intermediate.serialize_pem_with_signer(&root)?
+    );
+    fs::write(tls_dir.join("fullchain.pem"), chain)?;
+    fs::write(tls_dir.join("key.pem"), cert.serialize_private_key_pem())?;
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let port = get_free_port();
+    cmd.arg("serve")
+        .arg(&templates)
+        .arg(&content)
+        .arg(format!("0.0.0.0:{}", port))
+        .arg("--tls-cert")
+        .arg(tls_dir.join("fullchain.pem"))
+        .arg("--tls-key")
+        .arg(tls_dir.join("key.pem"))
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "listening on");
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(&rustls::Certificate(root.serialize_der()?))?;
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = rustls::ServerName::try_from("localhost")?;
+    let connection = rustls::ClientConnection::new(std::sync::Arc::new(config), server_name)?;
+    let stream = TcpStream::connect(format!("localhost:{}", port))?;
+    let mut stream = rustls::StreamOwned::new(connection, stream);
+    write!(
+        stream,
+        "GET /posts/post-1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("A subtitle"));
+    let peer_certificates = stream.conn.peer_certificates().unwrap_or_default();
+    assert_eq!(2, peer_certificates.len());
+    process.kill().unwrap();
+
+    fs::remove_dir_all(tls_dir)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn stops_gracefully_on_sigterm() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog_with_args(&["--grace-period", "5"])?;
+    assert_eq!(200, raw_get_status(&blog_url, "/")?);
+
+    let status = Command::new("kill")
+        .arg("-TERM")
+        .arg(process.id().to_string())
+        .status()?;
+    assert!(status.success());
+    assert!(process.wait()?.success());
+    // The listener is closed once the server stops.
+    assert!(raw_get_status(&blog_url, "/").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn serves_the_not_found_page() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    match ureq::get(&format!("{}/missing-page", blog_url)).call() {
+        Err(ureq::Error::Status(code, response)) => {
+            assert_eq!(404, code);
+            assert!(response
+                .into_string()?
+                .contains("404: nothing here in Title"));
+        }
+        _ => panic!("expected a not found response"),
+    }
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn returns_not_found_for_missing_content() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let paths = [
+        // Missing posts.
+        "/posts/missing",
+        "/posts/2023/missing",
+        // Missing assets.
+        "/assets/missing.css",
+        "/post_assets/missing.png",
+        "/post_assets/2023/bundle-post/missing.svg",
+        // The markdown files are never served.
+        "/posts/post-1.md",
+        "/post_assets/post-1.md",
+        "/post_assets/2023/bundle-post/index.md",
+    ];
+    for path in paths {
+        match ureq::get(&format!("{}{}", blog_url, path)).call() {
+            Err(ureq::Error::Status(code, _)) => assert_eq!(404, code, "{}", path),
+            Err(err) => panic!("error requesting {}: {}", path, err),
+            Ok(response) => panic!("{} returned {}", path, response.status()),
+        }
+    }
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn refuses_hostile_static_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let paths = [
+        // Parent segments, plain and encoded.
+        "/assets/../../content/blog.md",
+        "/post_assets/../blog.md",
+        "/post_assets/../../templates/index.html",
+        "/post_assets/%2e%2e/blog.md",
+        "/post_assets/%2E%2E%2Fblog.md",
+        "/post_assets/..%2f..%2ftemplates%2findex.html",
+        "/assets/%2e%2e/%2e%2e/content/blog.md",
+        "/post_assets/2023/bundle-post/..\\..\\post-1.md",
+        // Hidden files.
+        "/post_assets/.hidden",
+        "/assets/%2ehidden",
+        // Encoded nul bytes and invalid utf-8.
+        "/post_assets/diagram.svg%00.png",
+        "/post_assets/%ff",
+    ];
+    for path in paths {
+        let status = raw_get_status(&blog_url, path)?;
+        assert!((400..500).contains(&status), "{} returned {}", path, status);
+    }
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_symlinks_outside_the_static_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let linked_content = test_output_dir("symlinks-content");
+    copy_dir(PathBuf::from(&content).as_path(), &linked_content)?;
+    std::os::unix::fs::symlink(
+        linked_content.join("blog.md"),
+        linked_content.join("posts/outside.txt"),
+    )?;
+    std::os::unix::fs::symlink(
+        linked_content.join("posts/2023/bundle-post/diagram.svg"),
+        linked_content.join("posts/inside.svg"),
+    )?;
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let port = get_free_port();
+    cmd.arg("serve")
+        .arg(&templates)
+        .arg(&linked_content)
+        .arg(format!("0.0.0.0:{}", port))
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "listening on");
+    let blog_url = format!("http://localhost:{}", port);
+
+    assert_eq!(404, raw_get_status(&blog_url, "/post_assets/outside.txt")?);
+    // Symlinks to files in the static dirs are followed.
+    assert_eq!(200, raw_get_status(&blog_url, "/post_assets/inside.svg")?);
+    process.kill().unwrap();
+
+    fs::remove_dir_all(linked_content)?;
+    Ok(())
+}
+
+#[test]
+fn redirects_post_aliases() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    let agent = ureq::AgentBuilder::new().redirects(0).build();
+    let alias_url = format!("{}/old-post-1", blog_url);
+    let alias_result = agent.get(&alias_url).call()?;
+    assert_eq!(301, alias_result.status());
+    assert_eq!(Some("/posts/post-1"), alias_result.header("Location"));
+
+    process.kill().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn packs_without_removing_unknown_files() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("pack-unknown-files");
+    fs::create_dir_all(&output)?;
+    fs::write(output.join("CNAME"), "blog.example.com")?;
+    fs::create_dir_all(output.join("downloads/empty"))?;
+
+    // The output directory contains a file not generated by pack.
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack").arg(&templates).arg(&content).arg(&output);
+    cmd.assert().failure();
+    assert!(output.join("CNAME").exists());
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg("--force")
+        .arg(&templates)
+        .arg(&content)
+        .arg(&output);
+    cmd.assert().success();
+    assert!(output.join("index.html").exists());
+    assert!(output
+        .join("post_assets/2023/bundle-post/diagram.svg")
+        .exists());
+
+    // Once packed, the output directory can be packed again without forcing
+    // it, and the unknown files are kept.
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack").arg(&templates).arg(&content).arg(&output);
+    cmd.assert().success();
+    assert_eq!(
+        "blog.example.com",
+        fs::read_to_string(output.join("CNAME"))?
+    );
+    assert!(output.join("downloads/empty").is_dir());
+
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn packs_the_same_output_with_any_number_of_jobs() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let serial_output = test_output_dir("pack-serial");
+    let parallel_output = test_output_dir("pack-parallel");
+
+    for (jobs, output) in [("1", &serial_output), ("4", &parallel_output)] {
+        let mut cmd = Command::cargo_bin("wrustblog")?;
+        cmd.arg("pack")
+            .arg("--jobs")
+            .arg(jobs)
+            .arg(&templates)
+            .arg(&content)
+            .arg(output);
+        cmd.assert().success();
+    }
+    // The manifest lists every generated file with the key of its inputs.
+    let manifest = ".wrustblog-manifest.json";
+    assert_eq!(
+        fs::read_to_string(serial_output.join(manifest))?,
+        fs::read_to_string(parallel_output.join(manifest))?
+    );
+    assert_eq!(
+        fs::read_to_string(serial_output.join("index.html"))?,
+        fs::read_to_string(parallel_output.join("index.html"))?
+    );
+
+    fs::remove_dir_all(serial_output)?;
+    fs::remove_dir_all(parallel_output)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn packs_only_the_files_with_changed_inputs() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (content, templates) = test_blog_dir();
+    let changed_content = test_output_dir("incremental-content");
+    copy_dir(PathBuf::from(content).as_path(), &changed_content)?;
+    let changed_templates = test_output_dir("incremental-templates");
+    copy_dir(PathBuf::from(templates).as_path(), &changed_templates)?;
+    let output = test_output_dir("incremental-output");
+    let pack = |args: &[&str]| -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("wrustblog")?;
+        cmd.arg("pack")
+            .args(args)
+            .arg(&changed_templates)
+            .arg(&changed_content)
+            .arg(&output);
+        cmd.assert().success();
+        Ok(())
+    };
+    // The unchanged files are hard linked from the previous output, so they
+    // keep their inodes.
+    let rendered = [
+        "index.html",
+        "404.html",
+        "about.html",
+        "posts/post-1.html",
+        "posts/bundle-post.html",
+    ];
+    let inodes = || -> Vec<u64> {
+        rendered
+            .iter()
+            .map(|file| fs::metadata(output.join(file)).unwrap().ino())
+            .collect()
+    };
+
+    pack(&[])?;
+    let first = inodes();
+    pack(&[])?;
+    let unchanged = inodes();
+    assert_eq!(first, unchanged);
+
+    // Only the edited post and the home page, that lists it, are rendered.
+    let post_file = changed_content.join("posts/post-1.md");
+    let post = fs::read_to_string(&post_file)?;
+    fs::write(
+        &post_file,
+        post.replacen("a summary of the post", "an edited summary", 1),
+    )?;
+    pack(&[])?;
+    let edited = inodes();
+    assert_ne!(unchanged[0], edited[0]);
+    assert_eq!(unchanged[1..3], edited[1..3]);
+    assert_ne!(unchanged[3], edited[3]);
+    assert_eq!(unchanged[4], edited[4]);
+
+    // Every rendered file depends on the templates.
+    let footer_file = changed_templates.join("footer.html");
+    let footer = fs::read_to_string(&footer_file)?;
+    fs::write(&footer_file, format!("{}<!-- edited -->\n", footer))?;
+    pack(&[])?;
+    let retemplated = inodes();
+    for (before, after) in edited.iter().zip(&retemplated) {
+        assert_ne!(before, after);
+    }
+
+    pack(&["--full"])?;
+    for (before, after) in retemplated.iter().zip(&inodes()) {
+        assert_ne!(before, after);
+    }
+
+    // The files listed in a manifest without build keys, or in one that
+    // can't be read, are generated again.
+    let manifest = output.join(".wrustblog-manifest.json");
+    let files: Vec<String> = output_files(&output, Path::new(""))?
+        .into_iter()
+        .filter(|file| !file.starts_with('.'))
+        .map(|file| format!("\"{}\"", file))
+        .collect();
+    fs::write(&manifest, format!("{{\"files\": [{}]}}", files.join(", ")))?;
+    let listed = inodes();
+    pack(&[])?;
+    for (before, after) in listed.iter().zip(&inodes()) {
+        assert_ne!(before, after);
+    }
+    fs::write(&manifest, "not a manifest")?;
+    pack(&["--force"])?;
+
+    fs::remove_dir_all(changed_content)?;
+    fs::remove_dir_all(changed_templates)?;
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn packs_again_on_changes_when_watching() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let watched_content = test_output_dir("watch-content");
+    copy_dir(PathBuf::from(content).as_path(), &watched_content)?;
+    let output = test_output_dir("watch-output");
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg("--watch")
+        .arg(&templates)
+        .arg(&watched_content)
+        .arg(&output)
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "watching for changes");
+    assert!(!output.join("contact.html").exists());
+
+    fs::write(
+        watched_content.join("pages/contact.md"),
+        "---\ntitle: Contact\n---\nWrite me.\n",
+    )?;
+    wait_for_line(&mut process, "packed in");
+    assert!(output.join("contact.html").exists());
+    process.kill().unwrap();
+
+    fs::remove_dir_all(watched_content)?;
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn packs_precompressed_files() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("precompress");
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&content)
+        .arg(&output)
+        .arg("--precompress");
+    cmd.assert().success();
+
+    let css = output.join("assets/main.css");
+    let gzipped = output.join("assets/main.css.gz");
+    let mut decompressed = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(&gzipped)?).read_to_string(&mut decompressed)?;
+    assert_eq!(fs::read_to_string(&css)?, decompressed);
+    for compressed in [&gzipped, &output.join("assets/main.css.br")] {
+        assert!(fs::metadata(compressed)?.len() < fs::metadata(&css)?.len());
+        assert_eq!(
+            fs::metadata(&css)?.modified()?,
+            fs::metadata(compressed)?.modified()?
+        );
+    }
+    assert!(output.join("index.html.gz").is_file());
+    // Only the text files are compressed.
+    assert!(!output
+        .join("post_assets/2023/bundle-post/diagram.svg.gz")
+        .exists());
+
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn serves_a_packed_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("serve-static");
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack").arg(&templates).arg(&content).arg(&output);
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let port = get_free_port();
+    cmd.arg("serve-static")
+        .arg(&output)
+        .arg(format!("0.0.0.0:{}", port))
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "listening on");
+    let base_url = format!("http://localhost:{}", port);
+
+    let home = ureq::get(&format!("{}/", base_url)).call()?.into_string()?;
+    assert_eq!(fs::read_to_string(output.join("index.html"))?, home);
+
+    // Extension-less urls are served from the html file.
+    let page = ureq::get(&format!("{}/about", base_url))
+        .call()?
+        .into_string()?;
+    assert_eq!(fs::read_to_string(output.join("about.html"))?, page);
+
+    // Posts have the same url as when serving the blog.
+    let post = ureq::get(&format!("{}/posts/post-1", base_url))
+        .call()?
+        .into_string()?;
+    assert_eq!(fs::read_to_string(output.join("posts/post-1.html"))?, post);
+
+    // Directories redirect to their url with a trailing slash, always on the
+    // same site.
+    for path in ["/assets", "//assets", "///assets"] {
+        let (status, headers) = raw_get(&base_url, path, &[])?;
+        assert_eq!(301, status, "{}", path);
+        assert!(
+            headers.contains(&("location".into(), "/assets/".into())),
+            "{}",
+            path
+        );
+    }
+
+    match ureq::get(&format!("{}/missing", base_url)).call() {
+        Err(ureq::Error::Status(code, response)) => {
+            assert_eq!(404, code);
+            assert!(response
+                .into_string()?
+                .contains("404: nothing here in Title"));
+        }
+        _ => panic!("expected a not found response"),
+    }
+    process.kill().unwrap();
+
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn serves_urls_with_queries_and_encoded_paths() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) = serve_test_blog()?;
+
+    for path in [
+        "/?utm_source=x",
+        "/about?x=1",
+        "/posts/post-1?ref=feed",
+        "/posts/post-1#comments",
+        "/ab%6Fut",
+        "/assets/main.css?v=1",
+    ] {
+        let (status, _) = raw_get(&blog_url, path, &[])?;
+        assert_eq!(200, status, "{}", path);
+    }
+    let (status, _) = raw_get(&blog_url, "/posts/%2e%2e/blog.md", &[])?;
+    assert_eq!(404, status);
+
+    process.kill().unwrap();
+    Ok(())
+}
+
+#[test]
+fn serves_under_a_base_url() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut process, blog_url) =
+        serve_test_blog_with_args(&["--base-url", "https://example.com/blog/"])?;
+
+    let home = ureq::get(&format!("{}/blog/", blog_url))
+        .call()?
+        .into_string()?;
+    assert!(home.contains("href=\"/blog/posts/post-1\""));
+    let post = ureq::get(&format!("{}/blog/posts/bundle-post", blog_url))
+        .call()?
+        .into_string()?;
+    assert!(post.contains("href=\"/blog/\""));
+    assert!(post.contains("src=\"/blog/post_assets/2023/bundle-post/diagram.svg\""));
+    for path in [
+        "/blog/about",
+        "/blog/assets/main.css",
+        "/blog/post_assets/2023/bundle-post/diagram.svg",
+    ] {
+        assert_eq!(
+            200,
+            ureq::get(&format!("{}{}", blog_url, path)).call()?.status()
+        );
+    }
+
+    // Nothing is served outside the path of the base url.
+    for path in ["/", "/posts/post-1", "/about", "/assets/main.css"] {
+        let result = ureq::get(&format!("{}{}", blog_url, path)).call();
+        assert!(
+            matches!(result, Err(ureq::Error::Status(404, _))),
+            "{}",
+            path
+        );
+    }
+
+    process.kill().unwrap();
+    Ok(())
+}
+
+#[test]
+fn packs_under_a_base_url() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let output = test_output_dir("pack-base-url");
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg("--base-url")
+        .arg("https://example.com/blog/")
+        .arg(&templates)
+        .arg(&content)
+        .arg(&output);
+    cmd.assert().success();
+
+    let home = fs::read_to_string(output.join("index.html"))?;
+    assert!(home.contains("href=\"/blog/posts/post-1\""));
+    let post = fs::read_to_string(output.join("posts/bundle-post.html"))?;
+    assert!(post.contains("href=\"/blog/\""));
+    assert!(post.contains("src=\"/blog/post_assets/2023/bundle-post/diagram.svg\""));
+    let alias = fs::read_to_string(output.join("old-post-1.html"))?;
+    assert!(alias.contains("url=https://example.com/blog/posts/post-1"));
+
+    fs::remove_dir_all(output)?;
+    Ok(())
+}
+
+#[test]
+fn refuses_to_pack_posts_outside_the_output() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let hostile_content = test_output_dir("hostile-slug-content");
+    copy_dir(PathBuf::from(content).as_path(), &hostile_content)?;
+    let post_file = hostile_content.join("posts/post-1.md");
+    let post = fs::read_to_string(&post_file)?;
+    let output = test_output_dir("hostile-slug-output");
+
+    for (slug, blog_header) in [
+        ("../../escaped", ""),
+        ("escaped", "permalink: /:year/../../:slug\n"),
+    ] {
+        fs::write(
+            &post_file,
+            post.replacen("---\n", &format!("---\nslug: {}\n", slug), 1),
+        )?;
+        let blog_file = hostile_content.join("blog.md");
+        let blog = fs::read_to_string(&blog_file)?;
+        fs::write(
+            &blog_file,
+            blog.replacen("---\n", &format!("---\n{}", blog_header), 1),
+        )?;
+
+        let mut cmd = Command::cargo_bin("wrustblog")?;
+        cmd.arg("pack")
+            .arg(&templates)
+            .arg(&hostile_content)
+            .arg(&output);
+        cmd.assert().failure();
+        assert!(!output.parent().unwrap().join("escaped.html").exists());
+    }
+
+    fs::remove_dir_all(hostile_content)?;
+    Ok(())
+}
+
+#[test]
+fn refuses_aliases_of_other_urls() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let alias_content = test_output_dir("used-alias-content");
+    copy_dir(PathBuf::from(content).as_path(), &alias_content)?;
+    let post_file = alias_content.join("posts/post-1.md");
+    let post = fs::read_to_string(&post_file)?;
+    let output = test_output_dir("used-alias-output");
+
+    for alias in [
+        "/posts/post-1",
+        "/posts/bundle-post/",
+        "/about",
+        "/posts",
+        "/assets/main.css",
+        "/index",
+    ] {
+        fs::write(
+            &post_file,
+            post.replacen("    - /old-post-1", &format!("    - {}", alias), 1),
+        )?;
+        let mut cmd = Command::cargo_bin("wrustblog")?;
+        cmd.arg("pack")
+            .arg(&templates)
+            .arg(&alias_content)
+            .arg(&output);
+        cmd.assert().failure();
+
+        let mut cmd = Command::cargo_bin("wrustblog")?;
+        cmd.arg("serve")
+            .arg(&templates)
+            .arg(&alias_content)
+            .arg(format!("0.0.0.0:{}", get_free_port()));
+        cmd.assert().failure();
+    }
+
+    fs::remove_dir_all(alias_content)?;
+    Ok(())
+}
+
+#[test]
+fn refuses_to_pack_aliases_outside_the_output() -> Result<(), Box<dyn std::error::Error>> {
+    let (content, templates) = test_blog_dir();
+    let alias_content = test_output_dir("alias-content");
+    copy_dir(PathBuf::from(content).as_path(), &alias_content)?;
+    let post_file = alias_content.join("posts/post-1.md");
+    let post = fs::read_to_string(&post_file)?;
+    fs::write(
+        &post_file,
+        post.replacen("    - /old-post-1", "    - /../../escaped", 1),
+    )?;
+    let output = test_output_dir("alias-output");
+
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    cmd.arg("pack")
+        .arg(&templates)
+        .arg(&alias_content)
+        .arg(&output);
+    cmd.assert().failure();
+    assert!(!output.parent().unwrap().join("escaped.html").exists());
+
+    fs::remove_dir_all(alias_content)?;
+    Ok(())
+}
+
+/// Starts serving the test blog, returns the process of the server and the
+/// base url of the blog.
+fn serve_test_blog() -> Result<(Child, String), Box<dyn std::error::Error>> {
+    serve_test_blog_with_args(&[])
+}
+
+fn serve_test_blog_with_args(args: &[&str]) -> Result<(Child, String), Box<dyn std::error::Error>> {
+    let (content, _) = test_blog_dir();
+    serve_blog(Path::new(&content), args)
+}
+
+/// Serves the given content directory with the templates of the test blog.
+fn serve_blog(
+    content: &Path,
+    args: &[&str],
+) -> Result<(Child, String), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("wrustblog")?;
+    let (_, templates) = test_blog_dir();
+    let port = get_free_port();
+    let addr = format!("0.0.0.0:{}", port);
+    cmd.arg("serve")
+        .arg(templates)
+        .arg(content)
+        .arg(addr)
+        .args(args)
+        .stdout(Stdio::piped());
+    let mut process = cmd.spawn()?;
+    wait_for_line(&mut process, "listening on");
+    Ok((process, format!("http://localhost:{}", port)))
+}
+
 fn read_test_file(file_path: &str) -> String {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     path.push("tests");
@@ -56,20 +1279,38 @@ fn read_test_file(file_path: &str) -> String {
 }
 
 fn wait_for_line(p: &mut Child, line: &str) {
+    wait_for_lines(p, line, 1)
+}
+
+/// Waits until the process writes the given number of lines containing
+/// `line`, the output already read past that line is discarded.
+fn wait_for_lines(p: &mut Child, line: &str, count: usize) {
     thread::scope(|_| {
         let stdout = p.stdout.take().unwrap();
         let mut reader = BufReader::new(stdout);
-        let mut exit = false;
-        while !exit {
+        let mut found = 0;
+        while found < count {
             let mut read_line = String::new();
             reader.read_line(&mut read_line).unwrap();
             if read_line.contains(line) {
-                exit = true
+                found += 1
             }
         }
+        // Keep the output open, the process can write more lines.
+        p.stdout = Some(reader.into_inner());
     })
 }
 
+/// Returns a temporary path, that does not exist yet, for the output of a test.
+fn test_output_dir(name: &str) -> PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("wrustblog-{}-{}", name, std::process::id()));
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    path
+}
+
 fn test_blog_dir() -> (String, String) {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     path.push("tests/blog");
@@ -81,3 +1322,91 @@ fn test_blog_dir() -> (String, String) {
     let templates: String = templates.to_str().unwrap().try_into().unwrap();
     (content, templates)
 }
+
+/// Returns the paths of the files in the given directory, and in its
+/// subdirectories, relative to the `dir`.
+fn output_files(dir: &Path, current_path: &Path) -> std::io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir.join(current_path))? {
+        let entry = entry?;
+        let entry_path = current_path.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(output_files(dir, &entry_path)?);
+        } else {
+            files.push(entry_path.to_string_lossy().into());
+        }
+    }
+    Ok(files)
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &dest.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), dest.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends a GET request with the path as is, without the normalization done
+/// by http clients, and returns the status code of the response.
+fn raw_get_status(base_url: &str, path: &str) -> std::io::Result<u16> {
+    raw_get(base_url, path, &[]).map(|(status, _)| status)
+}
+
+/// Makes a GET request with the given headers without any processing of the
+/// request or the response, returns the status and the headers, with their
+/// names in lowercase, of the response.
+fn raw_get(
+    base_url: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> std::io::Result<(u16, Vec<(String, String)>)> {
+    raw_request("GET", base_url, path, headers)
+}
+
+/// Makes a request with the given method and headers without any processing
+/// of the request or the response, returns the status and the headers, with
+/// their names in lowercase, of the response.
+fn raw_request(
+    method: &str,
+    base_url: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> std::io::Result<(u16, Vec<(String, String)>)> {
+    let address = base_url.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(address)?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\n",
+        method, path, address
+    )?;
+    for (name, value) in headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(stream, "Connection: close\r\n\r\n")?;
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    // The status line looks like: HTTP/1.1 404 Not Found
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_default();
+    let mut response_headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            response_headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    Ok((status, response_headers))
+}